@@ -0,0 +1,682 @@
+//! Writing images out to common file formats.
+//! Every format is an `Encoder`, and `OutputFormat` picks which one to use.
+
+use super::{RgbaImage, ImageFormat, YCbCrMatrix};
+use std::io::{self, Write};
+use std::fmt::{Display, Formatter, Error};
+use std::error::Error as StdError;
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+/// The file formats an image can be encoded to
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Lossless PNG, with alpha
+    Png,
+    /// Lossy JPEG at the given quality (1-100), without alpha
+    Jpeg(u8),
+    /// Uncompressed 32-bit BMP
+    Bmp,
+    /// Lossless QOI (the "Quite OK Image" format), with alpha
+    Qoi,
+}
+
+impl OutputFormat {
+    /// Gets the encoder that writes this format
+    pub fn encoder(&self) -> Box<dyn Encoder> {
+        match *self {
+            OutputFormat::Png => Box::new(PngEncoder),
+            OutputFormat::Jpeg(quality) => Box::new(JpegEncoder(quality)),
+            OutputFormat::Bmp => Box::new(BmpEncoder),
+            OutputFormat::Qoi => Box::new(QoiEncoder),
+        }
+    }
+}
+
+/// Indicates errors while encoding an image
+#[derive(Debug)]
+pub enum EncodeError {
+    /// Writing the output failed
+    Io(io::Error),
+    /// The format can't store an image of this size
+    InvalidDimensions(usize, usize),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            EncodeError::Io(ref e) => write!(f, "could not write image: {}", e),
+            EncodeError::InvalidDimensions(w, h) => write!(f, "can't encode an image of size {}x{}", w, h),
+        }
+    }
+}
+
+impl StdError for EncodeError {
+    fn description(&self) -> &str {
+        match *self {
+            EncodeError::Io(_) => "IO error",
+            EncodeError::InvalidDimensions(..) => "Invalid dimensions",
+        }
+    }
+}
+
+impl From<io::Error> for EncodeError {
+    fn from(e: io::Error) -> EncodeError {
+        EncodeError::Io(e)
+    }
+}
+
+/// Writes an image in some file format
+pub trait Encoder {
+    /// Encodes `image`, writing it to `out`
+    fn encode(&self, image: &RgbaImage, out: &mut dyn Write) -> Result<(), EncodeError>;
+}
+
+// Gets the pixels of an image as 8-bit RGBA, row by row
+// NOTE: This uses the stored data, regardless of channel visibility
+fn rgba8(image: &RgbaImage) -> Vec<[u8; 4]> {
+    let ([r, g, b, a], _, _) = image.to_rgba_u8_planes();
+    (0..r.len()).map(|i| [r[i], g[i], b[i], a[i]]).collect()
+}
+
+// Checks that the size of an image fits in a u32, and isn't empty
+fn dimensions(image: &RgbaImage) -> Result<(u32, u32), EncodeError> {
+    let (w, h) = (image.width(), image.height());
+    if w == 0 || h == 0 || w > ::std::u32::MAX as usize || h > ::std::u32::MAX as usize {
+        return Err(EncodeError::InvalidDimensions(w, h))
+    }
+    Ok((w as u32, h as u32))
+}
+
+/// Writes PNG files
+// NOTE: The image data is stored, not compressed, as we don't have deflate yet
+pub struct PngEncoder;
+
+// CRC-32 (as used by PNG and zlib)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Adler-32 (the zlib checksum)
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+impl PngEncoder {
+    fn write_chunk(out: &mut dyn Write, kind: &[u8], data: &[u8]) -> Result<(), EncodeError> {
+        out.write_u32::<BigEndian>(data.len() as u32)?;
+        let mut body = kind.to_vec();
+        body.extend_from_slice(data);
+        out.write_all(&body)?;
+        out.write_u32::<BigEndian>(crc32(&body))?;
+        Ok(())
+    }
+}
+
+impl Encoder for PngEncoder {
+    fn encode(&self, image: &RgbaImage, out: &mut dyn Write) -> Result<(), EncodeError> {
+        let (w, h) = dimensions(image)?;
+        out.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+
+        let mut header = vec![];
+        header.write_u32::<BigEndian>(w)?;
+        header.write_u32::<BigEndian>(h)?;
+        // 8 bits per sample, RGBA, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        PngEncoder::write_chunk(out, b"IHDR", &header)?;
+
+        // Every row starts with its filter type (0, none)
+        let pixels = rgba8(image);
+        let mut raw = Vec::with_capacity(pixels.len() * 4 + h as usize);
+        for row in pixels.chunks(w as usize) {
+            raw.push(0);
+            for p in row {
+                raw.extend_from_slice(p);
+            }
+        }
+
+        // zlib stream made of stored deflate blocks
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+            zlib.write_u16::<LittleEndian>(block.len() as u16)?;
+            zlib.write_u16::<LittleEndian>(!(block.len() as u16))?;
+            zlib.extend_from_slice(block);
+        }
+        zlib.write_u32::<BigEndian>(adler32(&raw))?;
+        PngEncoder::write_chunk(out, b"IDAT", &zlib)?;
+        PngEncoder::write_chunk(out, b"IEND", &[])?;
+        Ok(())
+    }
+}
+
+/// Writes baseline JPEG files (JFIF, no chroma subsampling). Alpha is dropped
+pub struct JpegEncoder(pub u8);
+
+// The example quantization tables from the JPEG spec (Annex K), in row order
+const JPEG_LUMA_QUANT: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61,
+    12, 12, 14, 19, 26, 58, 60, 55,
+    14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62,
+    18, 22, 37, 56, 68, 109, 103, 77,
+    24, 35, 55, 64, 81, 104, 113, 92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+const JPEG_CHROMA_QUANT: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+// Where the nth coefficient of the zig-zag scan sits in row order
+const JPEG_ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+    12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The example Huffman tables from the JPEG spec (Annex K), as the number of codes of each
+// length (1 to 16 bits), then the values in code order
+const JPEG_LUMA_DC_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const JPEG_CHROMA_DC_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const JPEG_DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const JPEG_LUMA_AC_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const JPEG_LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+const JPEG_CHROMA_AC_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const JPEG_CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+// Scales a quantization table for `quality` (1-100), the same way libjpeg does
+fn jpeg_quant_table(base: &[u16; 64], quality: u8) -> [u16; 64] {
+    let quality = quality.max(1).min(100) as u32;
+    let scale = if quality < 50 { 5000 / quality } else { 200 - quality * 2 };
+    let mut table = [0; 64];
+    for (t, b) in table.iter_mut().zip(base.iter()) {
+        *t = ((*b as u32 * scale + 50) / 100).max(1).min(255) as u16;
+    }
+    table
+}
+
+// The (code, length) of every value of a Huffman table, indexed by value
+fn jpeg_huffman_codes(bits: &[u8; 16], values: &[u8]) -> [(u16, u8); 256] {
+    let mut codes = [(0, 0); 256];
+    let (mut code, mut at) = (0u16, 0);
+    for (len, &count) in bits.iter().enumerate() {
+        for _ in 0..count {
+            codes[values[at] as usize] = (code, len as u8 + 1);
+            code += 1;
+            at += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+// The number of bits needed for `v`, and those bits (negative values are stored as v - 1)
+fn jpeg_magnitude(v: i32) -> (u8, u16) {
+    let size = (32 - v.abs().leading_zeros()) as u8;
+    let bits = if v < 0 { v - 1 + (1 << size) } else { v };
+    (size, bits as u16)
+}
+
+// Packs Huffman codes into bytes, stuffing a 0 after every 0xff
+struct JpegBits {
+    data: Vec<u8>,
+    acc: u32,
+    count: u8,
+}
+
+impl JpegBits {
+    fn write(&mut self, bits: u16, len: u8) {
+        self.acc = (self.acc << len) | (bits as u32 & ((1 << len) - 1));
+        self.count += len;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.acc >> self.count) as u8;
+            self.data.push(byte);
+            if byte == 0xff {
+                self.data.push(0);
+            }
+        }
+        self.acc &= (1 << self.count) - 1;
+    }
+
+    // Fills the last byte with 1s
+    fn flush(&mut self) {
+        let pad = (8 - self.count % 8) % 8;
+        self.write(0xff, pad);
+    }
+}
+
+// The 1D DCT basis, scaled so applying it along rows and then columns gives the JPEG FDCT:
+// entry [u][x] is c(u) / 2 * cos((2x + 1) * u * pi / 16), with c(0) = 1 / sqrt(2) and c(u) = 1
+fn jpeg_dct_table() -> [[f32; 8]; 8] {
+    use std::f32::consts::{PI, FRAC_1_SQRT_2};
+
+    let mut table = [[0.0; 8]; 8];
+    for (u, row) in table.iter_mut().enumerate() {
+        let c = if u == 0 { FRAC_1_SQRT_2 } else { 1.0 };
+        for (x, v) in row.iter_mut().enumerate() {
+            *v = 0.5 * c * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+        }
+    }
+    table
+}
+
+// One component of the scan: its quantization table, Huffman codes and last DC value
+struct JpegComponent {
+    quant: [u16; 64],
+    dc: [(u16, u8); 256],
+    ac: [(u16, u8); 256],
+    prev_dc: i32,
+}
+
+impl JpegComponent {
+    // Transforms, quantizes and codes one 8x8 block of level-shifted samples (in row order).
+    // `dct` comes from jpeg_dct_table
+    fn encode_block(&mut self, block: &[f32; 64], dct: &[[f32; 8]; 8], bits: &mut JpegBits) {
+        // Forward DCT, done separably: first along each row, then down each column
+        let mut rows = [0.0f32; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8).map(|x| block[y * 8 + x] * dct[u][x]).sum();
+            }
+        }
+        let mut coeffs = [0i32; 64];
+        for v in 0..8 {
+            for u in 0..8 {
+                let sum: f32 = (0..8).map(|y| rows[y * 8 + u] * dct[v][y]).sum();
+                let i = v * 8 + u;
+                coeffs[i] = (sum / self.quant[i] as f32).round() as i32;
+            }
+        }
+
+        let (size, value) = jpeg_magnitude(coeffs[0] - self.prev_dc);
+        self.prev_dc = coeffs[0];
+        bits.write(self.dc[size as usize].0, self.dc[size as usize].1);
+        bits.write(value, size);
+
+        let mut zeros = 0;
+        for &i in JPEG_ZIGZAG[1..].iter() {
+            if coeffs[i] == 0 {
+                zeros += 1;
+                continue
+            }
+            // Runs of 16 zeros get their own code (ZRL)
+            while zeros > 15 {
+                bits.write(self.ac[0xf0].0, self.ac[0xf0].1);
+                zeros -= 16;
+            }
+            let (size, value) = jpeg_magnitude(coeffs[i]);
+            let symbol = (zeros << 4) as usize | size as usize;
+            bits.write(self.ac[symbol].0, self.ac[symbol].1);
+            bits.write(value, size);
+            zeros = 0;
+        }
+        if zeros > 0 {
+            // End of block
+            bits.write(self.ac[0].0, self.ac[0].1);
+        }
+    }
+}
+
+impl JpegEncoder {
+    fn write_segment(out: &mut dyn Write, marker: u8, data: &[u8]) -> Result<(), EncodeError> {
+        out.write_all(&[0xff, marker])?;
+        out.write_u16::<BigEndian>(data.len() as u16 + 2)?;
+        out.write_all(data)?;
+        Ok(())
+    }
+}
+
+impl Encoder for JpegEncoder {
+    fn encode(&self, image: &RgbaImage, out: &mut dyn Write) -> Result<(), EncodeError> {
+        let (w, h) = dimensions(image)?;
+        if w > 0xffff || h > 0xffff {
+            return Err(EncodeError::InvalidDimensions(image.width(), image.height()))
+        }
+        let (luma_quant, chroma_quant) = (jpeg_quant_table(&JPEG_LUMA_QUANT, self.0), jpeg_quant_table(&JPEG_CHROMA_QUANT, self.0));
+
+        out.write_all(&[0xff, 0xd8])?;
+        // JFIF 1.1, no density or thumbnail
+        JpegEncoder::write_segment(out, 0xe0, &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0])?;
+        let mut tables = vec![];
+        for (id, quant) in [luma_quant, chroma_quant].iter().enumerate() {
+            tables.push(id as u8);
+            tables.extend(JPEG_ZIGZAG.iter().map(|&i| quant[i] as u8));
+        }
+        JpegEncoder::write_segment(out, 0xdb, &tables)?;
+        // 8-bit samples, then Y, Cb and Cr, all at full resolution
+        let mut frame = vec![8];
+        frame.write_u16::<BigEndian>(h as u16)?;
+        frame.write_u16::<BigEndian>(w as u16)?;
+        frame.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        JpegEncoder::write_segment(out, 0xc0, &frame)?;
+        let mut huffman = vec![];
+        for &(class_id, bits, values) in [
+            (0x00, &JPEG_LUMA_DC_BITS, &JPEG_DC_VALUES[..]),
+            (0x10, &JPEG_LUMA_AC_BITS, &JPEG_LUMA_AC_VALUES[..]),
+            (0x01, &JPEG_CHROMA_DC_BITS, &JPEG_DC_VALUES[..]),
+            (0x11, &JPEG_CHROMA_AC_BITS, &JPEG_CHROMA_AC_VALUES[..]),
+        ].iter() {
+            huffman.push(class_id);
+            huffman.extend_from_slice(bits);
+            huffman.extend_from_slice(values);
+        }
+        JpegEncoder::write_segment(out, 0xc4, &huffman)?;
+        JpegEncoder::write_segment(out, 0xda, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0])?;
+
+        // Convert to YCbCr (full range, as JFIF wants), shifted to be centered on 0
+        let matrix = YCbCrMatrix::Bt601;
+        let planes: Vec<[f32; 3]> = rgba8(image).iter().map(|p| {
+            let (y, cb, cr) = matrix.rgb_to_ycbcr(p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0);
+            [y * 255.0 - 128.0, (cb - 0.5) * 255.0, (cr - 0.5) * 255.0]
+        }).collect();
+
+        let mut components = [
+            JpegComponent { quant: luma_quant, dc: jpeg_huffman_codes(&JPEG_LUMA_DC_BITS, &JPEG_DC_VALUES),
+                ac: jpeg_huffman_codes(&JPEG_LUMA_AC_BITS, &JPEG_LUMA_AC_VALUES), prev_dc: 0 },
+            JpegComponent { quant: chroma_quant, dc: jpeg_huffman_codes(&JPEG_CHROMA_DC_BITS, &JPEG_DC_VALUES),
+                ac: jpeg_huffman_codes(&JPEG_CHROMA_AC_BITS, &JPEG_CHROMA_AC_VALUES), prev_dc: 0 },
+            JpegComponent { quant: chroma_quant, dc: jpeg_huffman_codes(&JPEG_CHROMA_DC_BITS, &JPEG_DC_VALUES),
+                ac: jpeg_huffman_codes(&JPEG_CHROMA_AC_BITS, &JPEG_CHROMA_AC_VALUES), prev_dc: 0 },
+        ];
+        let mut bits = JpegBits { data: vec![], acc: 0, count: 0 };
+        let dct = jpeg_dct_table();
+        let (w, h) = (w as usize, h as usize);
+        // Blocks hanging off the right or bottom edge repeat the edge pixels
+        for by in 0..(h + 7) / 8 {
+            for bx in 0..(w + 7) / 8 {
+                for (c, component) in components.iter_mut().enumerate() {
+                    let mut block = [0.0; 64];
+                    for (i, v) in block.iter_mut().enumerate() {
+                        let (x, y) = ((bx * 8 + i % 8).min(w - 1), (by * 8 + i / 8).min(h - 1));
+                        *v = planes[y * w + x][c];
+                    }
+                    component.encode_block(&block, &dct, &mut bits);
+                }
+            }
+        }
+        bits.flush();
+        out.write_all(&bits.data)?;
+        out.write_all(&[0xff, 0xd9])?;
+        Ok(())
+    }
+}
+
+/// Writes 32-bit BMP files
+pub struct BmpEncoder;
+
+impl Encoder for BmpEncoder {
+    fn encode(&self, image: &RgbaImage, out: &mut dyn Write) -> Result<(), EncodeError> {
+        let (w, h) = dimensions(image)?;
+        let data_size = w as u64 * h as u64 * 4;
+        if 54 + data_size > ::std::u32::MAX as u64 || w > ::std::i32::MAX as u32 || h > ::std::i32::MAX as u32 {
+            return Err(EncodeError::InvalidDimensions(image.width(), image.height()))
+        }
+
+        // File header
+        out.write_all(b"BM")?;
+        out.write_u32::<LittleEndian>(54 + data_size as u32)?;
+        out.write_u32::<LittleEndian>(0)?;
+        out.write_u32::<LittleEndian>(54)?;
+        // Info header: 32 bits per pixel, uncompressed, 72 DPI
+        out.write_u32::<LittleEndian>(40)?;
+        out.write_u32::<LittleEndian>(w)?;
+        out.write_u32::<LittleEndian>(h)?;
+        out.write_u16::<LittleEndian>(1)?;
+        out.write_u16::<LittleEndian>(32)?;
+        out.write_u32::<LittleEndian>(0)?;
+        out.write_u32::<LittleEndian>(data_size as u32)?;
+        out.write_u32::<LittleEndian>(2835)?;
+        out.write_u32::<LittleEndian>(2835)?;
+        out.write_u32::<LittleEndian>(0)?;
+        out.write_u32::<LittleEndian>(0)?;
+
+        // Rows go bottom to top, pixels are stored BGRA
+        let pixels = rgba8(image);
+        let mut data = Vec::with_capacity(data_size as usize);
+        for row in pixels.chunks(w as usize).rev() {
+            for p in row {
+                data.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+            }
+        }
+        out.write_all(&data)?;
+        Ok(())
+    }
+}
+
+/// Writes QOI files
+pub struct QoiEncoder;
+
+impl Encoder for QoiEncoder {
+    fn encode(&self, image: &RgbaImage, out: &mut dyn Write) -> Result<(), EncodeError> {
+        let (w, h) = dimensions(image)?;
+        let mut data = b"qoif".to_vec();
+        data.write_u32::<BigEndian>(w)?;
+        data.write_u32::<BigEndian>(h)?;
+        // 4 channels, sRGB with linear alpha
+        data.extend_from_slice(&[4, 0]);
+
+        let mut seen = [[0u8; 4]; 64];
+        let mut prev = [0u8, 0, 0, 255];
+        let mut run = 0u8;
+        let pixels = rgba8(image);
+        for (i, p) in pixels.iter().enumerate() {
+            if *p == prev {
+                run += 1;
+                if run == 62 || i + 1 == pixels.len() {
+                    data.push(0xc0 | (run - 1));
+                    run = 0;
+                }
+                continue
+            }
+            if run > 0 {
+                data.push(0xc0 | (run - 1));
+                run = 0;
+            }
+
+            let hash = (p[0] as usize * 3 + p[1] as usize * 5 + p[2] as usize * 7 + p[3] as usize * 11) % 64;
+            if seen[hash] == *p {
+                data.push(hash as u8);
+            } else {
+                seen[hash] = *p;
+                if p[3] == prev[3] {
+                    let dr = p[0].wrapping_sub(prev[0]) as i8;
+                    let dg = p[1].wrapping_sub(prev[1]) as i8;
+                    let db = p[2].wrapping_sub(prev[2]) as i8;
+                    let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                    if dr >= -2 && dr <= 1 && dg >= -2 && dg <= 1 && db >= -2 && db <= 1 {
+                        data.push(0x40 | (((dr + 2) as u8) << 4) | (((dg + 2) as u8) << 2) | (db + 2) as u8);
+                    } else if dg >= -32 && dg <= 31 && dr_dg >= -8 && dr_dg <= 7 && db_dg >= -8 && db_dg <= 7 {
+                        data.push(0x80 | (dg + 32) as u8);
+                        data.push((((dr_dg + 8) as u8) << 4) | (db_dg + 8) as u8);
+                    } else {
+                        data.extend_from_slice(&[0xfe, p[0], p[1], p[2]]);
+                    }
+                } else {
+                    data.extend_from_slice(&[0xff, p[0], p[1], p[2], p[3]]);
+                }
+            }
+            prev = *p;
+        }
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        out.write_all(&data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, adler32, OutputFormat, EncodeError};
+    use format::{RgbaImage, ImageFormat};
+    use palette::Colora;
+
+    fn sample() -> RgbaImage {
+        let mut image = RgbaImage::new(3, 2);
+        image.set_pixel(0, 0, Colora::rgb(1.0, 0.0, 0.0, 1.0)).unwrap();
+        image.set_pixel(1, 0, Colora::rgb(0.0, 1.0, 0.0, 1.0)).unwrap();
+        image.set_pixel(2, 0, Colora::rgb(0.0, 0.0, 1.0, 0.0)).unwrap();
+        image
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn encode_png() {
+        let mut out = vec![];
+        sample().encode(&mut out, OutputFormat::Png).unwrap();
+        assert_eq!(&out[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[16..25], &[0, 0, 0, 3, 0, 0, 0, 2, 8]);
+        // Stored block: header, then the filter byte and the first pixel
+        assert_eq!(&out[33 + 8..33 + 8 + 2], &[0x78, 0x01]);
+        assert_eq!(&out[33 + 8 + 7..33 + 8 + 12], &[0, 255, 0, 0, 255]);
+        assert_eq!(&out[out.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn encode_bmp() {
+        let mut out = vec![];
+        sample().encode(&mut out, OutputFormat::Bmp).unwrap();
+        assert_eq!(out.len(), 54 + 3 * 2 * 4);
+        assert_eq!(&out[..2], b"BM");
+        // The bottom row (black) comes first, then the top row in BGRA
+        assert_eq!(&out[54..58], &[0, 0, 0, 255]);
+        assert_eq!(&out[66..78], &[0, 0, 255, 255, 0, 255, 0, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn encode_qoi() {
+        let mut out = vec![];
+        sample().encode(&mut out, OutputFormat::Qoi).unwrap();
+        assert_eq!(&out[..14], &[b'q', b'o', b'i', b'f', 0, 0, 0, 3, 0, 0, 0, 2, 4, 0]);
+        assert_eq!(&out[14..], &[
+            0x5a, // red, a small (wrapping) difference from black
+            0x76, // green, likewise from red
+            0xff, 0, 0, 255, 0, // transparent blue
+            0xff, 0, 0, 0, 255, // black
+            0xc1, // 2 more black
+            0, 0, 0, 0, 0, 0, 0, 1
+        ][..]);
+    }
+
+    #[test]
+    fn encode_jpeg() {
+        let mut image = RgbaImage::new(19, 10);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 18.0, y as f32 / 9.0, 0.5, 1.0));
+        let mut out = vec![];
+        image.encode(&mut out, OutputFormat::Jpeg(90)).unwrap();
+        assert_eq!(&out[..2], &[0xff, 0xd8]);
+        assert_eq!(&out[out.len() - 2..], &[0xff, 0xd9]);
+
+        // Walk the segments up to the scan, checking the frame header on the way
+        let mut at = 2;
+        let mut markers = vec![];
+        while out[at + 1] != 0xda {
+            assert_eq!(out[at], 0xff);
+            let len = ((out[at + 2] as usize) << 8) | out[at + 3] as usize;
+            if out[at + 1] == 0xc0 {
+                assert_eq!(&out[at + 5..at + 9], &[0, 10, 0, 19]);
+            }
+            markers.push(out[at + 1]);
+            at += 2 + len;
+        }
+        assert_eq!(markers, vec![0xe0, 0xdb, 0xc0, 0xc4]);
+        // Inside the scan, every 0xff is stuffed
+        let scan = &out[at + 14..out.len() - 2];
+        assert!(scan.windows(2).all(|p| p[0] != 0xff || p[1] == 0));
+
+        // Lower quality means smaller files
+        let mut low = vec![];
+        image.encode(&mut low, OutputFormat::Jpeg(10)).unwrap();
+        assert!(low.len() < out.len());
+    }
+
+    #[test]
+    fn jpeg_tables() {
+        use super::{JPEG_LUMA_QUANT, JPEG_LUMA_AC_BITS, JPEG_LUMA_AC_VALUES, jpeg_quant_table, jpeg_huffman_codes, jpeg_magnitude,
+            jpeg_dct_table};
+
+        assert_eq!(jpeg_quant_table(&JPEG_LUMA_QUANT, 50), JPEG_LUMA_QUANT);
+        assert!(jpeg_quant_table(&JPEG_LUMA_QUANT, 100).iter().all(|q| *q == 1));
+        assert_eq!(jpeg_quant_table(&JPEG_LUMA_QUANT, 1)[0], 255);
+        assert_eq!(JPEG_LUMA_AC_BITS.iter().map(|b| *b as usize).sum::<usize>(), JPEG_LUMA_AC_VALUES.len());
+        // End of block is 1010, ZRL is 11111111001 (from the spec)
+        let codes = jpeg_huffman_codes(&JPEG_LUMA_AC_BITS, &JPEG_LUMA_AC_VALUES);
+        assert_eq!(codes[0x00], (0b1010, 4));
+        assert_eq!(codes[0xf0], (0b111_1111_1001, 11));
+        assert_eq!(jpeg_magnitude(0), (0, 0));
+        assert_eq!(jpeg_magnitude(5), (3, 5));
+        assert_eq!(jpeg_magnitude(-5), (3, 2));
+        // The DCT basis is orthonormal
+        let dct = jpeg_dct_table();
+        for u in 0..8 {
+            for v in 0..8 {
+                let dot: f32 = (0..8).map(|x| dct[u][x] * dct[v][x]).sum();
+                assert!((dot - if u == v { 1.0 } else { 0.0 }).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_errors() {
+        let mut out = vec![];
+        match RgbaImage::new(0, 4).encode(&mut out, OutputFormat::Png) {
+            Err(EncodeError::InvalidDimensions(0, 4)) => {},
+            other => panic!("expected InvalidDimensions, got {:?}", other)
+        }
+        match RgbaImage::new(0x1_0000, 1).encode(&mut out, OutputFormat::Jpeg(75)) {
+            Err(EncodeError::InvalidDimensions(0x1_0000, 1)) => {},
+            other => panic!("expected InvalidDimensions, got {:?}", other)
+        }
+        assert!(out.is_empty());
+    }
+}
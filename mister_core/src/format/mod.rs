@@ -1,13 +1,18 @@
 //! This modules wraps various image models, using image::Image as its backing.
 use super::{Channel, Image};
-use palette::Colora; // Use Colora as a generic color.
+use image::ChannelError;
+use palette::{Colora, Rgba}; // Use Colora as a generic color.
 use std::fmt::Debug;
 use std::error::Error;
 
 mod rgba;
 mod hsla;
+mod ycbcr;
+mod encode;
 
-pub use self::rgba::{RgbaImage, RgbaImageError, RgbaChannel};
+pub use self::rgba::{RgbaImage, RgbaImageError, RgbaChannel, RawPlanar, channel_correlation_matrix};
+pub use self::ycbcr::{YCbCrImage, YCbCrChannel, ChromaSubsampling, YCbCrMatrix};
+pub use self::encode::{OutputFormat, EncodeError, Encoder, PngEncoder, JpegEncoder, BmpEncoder, QoiEncoder};
 
 // TODO error_chain this!
 /// Indicates errors for image formats
@@ -17,6 +22,14 @@ pub enum ImageFormatError<T> {
     OutOfBounds(usize, usize),
     /// This channel doesn't have a value at that location
     MissingData(T, usize, usize),
+    /// An operation on a channel failed
+    Channel(ChannelError),
+}
+
+impl<T> From<ChannelError> for ImageFormatError<T> {
+    fn from(e: ChannelError) -> ImageFormatError<T> {
+        ImageFormatError::Channel(e)
+    }
 }
 
 /// Describes a general interface for formatted images
@@ -30,11 +43,24 @@ pub trait ImageFormat<T: Clone + Debug> {
     // type PixelError = ImageFormatError<Self::ChannelName>;
     /// The number of channels this image uses
     fn channel_count(&self) -> usize;
+    /// Lists every channel this image uses
+    fn channel_names(&self) -> Vec<Self::ChannelName>;
     // NOTE Confuing name QUESTION How do we fix?
     /// Enables/disables the specified channel
     fn set_channel_visible(&mut self, &Self::ChannelName, bool);
     /// Gets the "visibility" of the specified channel
     fn is_channel_visible(&self, &Self::ChannelName) -> bool;
+    /// Whether tone adjustments (brightness, contrast, gamma and invert) apply to the specified
+    /// channel. They change the stored values of every such channel, visible or not. Formats
+    /// should say no for channels like alpha
+    fn is_color_channel(&self, _: &Self::ChannelName) -> bool {
+        true
+    }
+    /// Whether the specified channel holds alpha, which compositing blends by. Formats without
+    /// one keep the default
+    fn is_alpha_channel(&self, _: &Self::ChannelName) -> bool {
+        false
+    }
     /// Gets an underlying channel
     fn channel(&self, &Self::ChannelName) -> &Channel<T>;
     /// Gets an underlying channel mutably
@@ -44,6 +70,8 @@ pub trait ImageFormat<T: Clone + Debug> {
     fn width(&self) -> usize;
     /// Gets the height of the image
     fn height(&self) -> usize;
+    /// Creates a blank image of the same format and channel visibility, sized w x h
+    fn with_dimensions(&self, w: usize, h: usize) -> Self where Self: Sized;
 
     /// Gets color at (x, y)
     fn pixel(&self, x: usize, y: usize) -> Result<Colora, ImageFormatError<Self::ChannelName>>;
@@ -63,4 +91,490 @@ pub trait ImageFormat<T: Clone + Debug> {
     fn flat_data(&self) -> Vec<T> {
         self.data().iter().flat_map(|x| x).cloned().collect()
     }
+
+    /// Checks that both images (possibly of different formats) have the same size and exactly the
+    /// same stored values at every pixel. Visibility is ignored
+    fn deep_equal<F: ImageFormat<T>>(&self, other: &F) -> bool where T: PartialEq {
+        self.width() == other.width() && self.height() == other.height() && self.data() == other.data()
+    }
+
+    /// Mirrors every row of every channel left-to-right
+    fn flip_horizontal(&mut self) -> Result<(), ImageFormatError<Self::ChannelName>> {
+        let (w, h) = (self.width(), self.height());
+        for name in self.channel_names() {
+            let c = self.channel_mut(&name);
+            if c.len() < w * h {
+                return Err(ImageFormatError::MissingData(name, c.len() % w.max(1), c.len() / w.max(1)))
+            }
+            for y in 0..h {
+                for x in 0..w/2 {
+                    let (a, b) = (y*w + x, y*w + w - 1 - x);
+                    let (va, vb) = (c[a].clone(), c[b].clone());
+                    c.write(a, vb);
+                    c.write(b, va);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Swaps whole rows of every channel top-to-bottom
+    fn flip_vertical(&mut self) -> Result<(), ImageFormatError<Self::ChannelName>> {
+        let (w, h) = (self.width(), self.height());
+        for name in self.channel_names() {
+            let c = self.channel_mut(&name);
+            if c.len() < w * h {
+                return Err(ImageFormatError::MissingData(name, c.len() % w.max(1), c.len() / w.max(1)))
+            }
+            for y in 0..h/2 {
+                let (top, bottom) = (y*w, (h - 1 - y)*w);
+                for x in 0..w {
+                    let (va, vb) = (c[top + x].clone(), c[bottom + x].clone());
+                    c.write(top + x, vb);
+                    c.write(bottom + x, va);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Turns the image 180° in place, by reversing every channel
+    fn rotate180(&mut self) {
+        for name in self.channel_names() {
+            let c = self.channel_mut(&name);
+            let len = c.len();
+            for i in 0..len/2 {
+                let (va, vb) = (c[i].clone(), c[len - 1 - i].clone());
+                c.write(i, vb);
+                c.write(len - 1 - i, va);
+            }
+        }
+    }
+
+    /// Creates a new image from the `w` x `h` rectangle with its top-left corner at (x, y).
+    /// Channel visibility is kept
+    fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<Self, ImageFormatError<Self::ChannelName>> where Self: Sized {
+        let fits = |start: usize, len: usize, max: usize| start.checked_add(len).map_or(false, |end| end <= max);
+        if !fits(x, w, self.width()) || !fits(y, h, self.height()) {
+            return Err(ImageFormatError::OutOfBounds(x.saturating_add(w).saturating_sub(1), y.saturating_add(h).saturating_sub(1)))
+        }
+        let width = self.width();
+        let mut out = self.with_dimensions(w, h);
+        for name in self.channel_names() {
+            let src = self.channel(&name);
+            if src.len() < width * self.height() {
+                return Err(ImageFormatError::MissingData(name, src.len() % width.max(1), src.len() / width.max(1)))
+            }
+            let dst = out.channel_mut(&name);
+            for row in 0..h {
+                let start = (y + row)*width + x;
+                dst.write_slice(row * w, &src.as_raw()[start..start + w])?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Creates a copy of this image scaled to `new_w` x `new_h`, taking each pixel from the
+    /// nearest source pixel. Works on the stored values, so visibility doesn't matter; channels
+    /// that don't hold a value for every pixel are left blank
+    fn resize_nearest(&self, new_w: usize, new_h: usize) -> Self where Self: Sized {
+        let (w, h) = (self.width(), self.height());
+        resample_channels(self, new_w, new_h, |_, src, x, y| src[(y * h / new_h)*w + x * w / new_w].clone())
+    }
+
+    /// Creates a copy of this image scaled to `new_w` x `new_h`, blending each pixel from the
+    /// four nearest source pixels. Works on the stored values, so visibility doesn't matter;
+    /// channels that don't hold a value for every pixel are left blank
+    fn resize_bilinear(&self, new_w: usize, new_h: usize) -> Self where Self: Sized, T: From<f32> + Into<f32> {
+        let (w, h) = (self.width(), self.height());
+        let (sx, sy) = (w as f32 / new_w as f32, h as f32 / new_h as f32);
+        resample_channels(self, new_w, new_h, |_, src, x, y| {
+            // Line up pixel centers, not corners
+            T::from(bilinear_value(src, w, h, (x as f32 + 0.5) * sx - 0.5, (y as f32 + 0.5) * sy - 0.5))
+        })
+    }
+
+    /// Creates a copy of this image scaled to `new_w` x `new_h`, blending each pixel from the
+    /// 4x4 nearest source pixels with a Catmull-Rom curve. Results are clamped to [0, 1]. Works on
+    /// the stored values, so visibility doesn't matter; channels that don't hold a value for every
+    /// pixel are left blank
+    fn resize_bicubic(&self, new_w: usize, new_h: usize) -> Self where Self: Sized, T: From<f32> + Into<f32> {
+        let (w, h) = (self.width(), self.height());
+        let (scale_x, scale_y) = (w as f32 / new_w as f32, h as f32 / new_h as f32);
+        resample_channels(self, new_w, new_h, |_, src, x, y| {
+            let at = |x: isize, y: isize| -> f32 {
+                let (x, y) = (x.max(0).min(w as isize - 1) as usize, y.max(0).min(h as isize - 1) as usize);
+                src[y*w + x].clone().into()
+            };
+            // Line up pixel centers, not corners
+            let (sx, sy) = ((x as f32 + 0.5) * scale_x - 0.5, (y as f32 + 0.5) * scale_y - 0.5);
+            let (x0, y0) = (sx.floor() as isize, sy.floor() as isize);
+            let mut sum = 0.0;
+            for j in -1..3 {
+                let wy = catmull_rom(sy - (y0 + j) as f32);
+                for i in -1..3 {
+                    sum += wy * catmull_rom(sx - (x0 + i) as f32) * at(x0 + i, y0 + j);
+                }
+            }
+            T::from(sum.max(0.0).min(1.0))
+        })
+    }
+
+    /// Creates a copy of this image `factor` times smaller, averaging each `factor` x `factor`
+    /// block into one pixel. `factor` must divide both the width and the height. Works on the
+    /// stored values, so visibility doesn't matter
+    fn downsample(&self, factor: usize) -> Result<Self, ImageFormatError<Self::ChannelName>> where Self: Sized, T: From<f32> + Into<f32> {
+        let (w, h) = (self.width(), self.height());
+        if factor == 0 {
+            return Err(ChannelError::ZeroStep.into())
+        }
+        for &len in &[w, h] {
+            if len % factor != 0 {
+                return Err(ChannelError::NotDivisible { len: len, divisor: factor }.into())
+            }
+        }
+        for name in self.channel_names() {
+            let len = self.channel(&name).len();
+            if len < w * h {
+                return Err(ImageFormatError::MissingData(name, len % w.max(1), len / w.max(1)))
+            }
+        }
+        let area = (factor * factor) as f32;
+        Ok(resample_channels(self, w / factor, h / factor, |_, src, x, y| {
+            let mut sum = 0.0;
+            for sy in y*factor..(y + 1)*factor {
+                for sx in x*factor..(x + 1)*factor {
+                    sum += Into::<f32>::into(src[sy*w + sx].clone());
+                }
+            }
+            T::from(sum / area)
+        }))
+    }
+
+    /// Copies every channel of `src` into this image with its top-left corner at (x, y).
+    /// Whatever falls outside this image is left out
+    fn paste(&mut self, src: &Self, x: usize, y: usize) -> Result<(), ImageFormatError<Self::ChannelName>> where Self: Sized {
+        let (w, h) = (self.width(), self.height());
+        let (src_w, src_h) = (src.width(), src.height());
+        let (copy_w, copy_h) = (src_w.min(w.saturating_sub(x)), src_h.min(h.saturating_sub(y)));
+        if copy_w == 0 || copy_h == 0 {
+            return Ok(())
+        }
+        for name in src.channel_names() {
+            let from = src.channel(&name);
+            if from.len() < src_w * src_h {
+                return Err(ImageFormatError::MissingData(name, from.len() % src_w.max(1), from.len() / src_w.max(1)))
+            }
+            let to = self.channel_mut(&name);
+            for row in 0..copy_h {
+                to.write_slice((y + row)*w + x, &from.as_raw()[row*src_w..row*src_w + copy_w])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Blends `src` over this image with its top-left corner at (x, y), using each source
+    /// pixel's alpha (the "source over" operator). Whatever falls outside this image is left out.
+    /// Works on the stored values, so visibility doesn't matter; without an alpha channel, pixels
+    /// count as opaque
+    fn composite(&mut self, src: &Self, x: usize, y: usize) where Self: Sized, T: From<f32> + Into<f32> {
+        let (w, h) = (self.width(), self.height());
+        let (src_w, src_h) = (src.width(), src.height());
+        let (copy_w, copy_h) = (src_w.min(w.saturating_sub(x)), src_h.min(h.saturating_sub(y)));
+        if copy_w == 0 || copy_h == 0 {
+            return
+        }
+        // Read both alphas up front, since blending overwrites the destination's
+        let read_alpha = |img: &Self, stride: usize, ox: usize, oy: usize| -> Vec<f32> {
+            let values = img.channel_names().into_iter()
+                .find(|name| img.is_alpha_channel(name))
+                .map(|name| img.channel(&name).as_raw());
+            (0..copy_w * copy_h).map(|i| {
+                values.and_then(|v| v.get((oy + i / copy_w)*stride + ox + i % copy_w))
+                    .map_or(1.0, |a| a.clone().into())
+            }).collect()
+        };
+        let (sa, da) = (read_alpha(src, src_w, 0, 0), read_alpha(self, w, x, y));
+        for name in src.channel_names() {
+            let is_alpha = self.is_alpha_channel(&name);
+            let from = src.channel(&name).as_raw();
+            let to = self.channel_mut(&name);
+            if from.len() < src_w * src_h || to.len() < w * h {
+                continue
+            }
+            for i in 0..copy_w * copy_h {
+                let (sx, sy) = (i % copy_w, i / copy_w);
+                let at = (y + sy)*w + x + sx;
+                let a = sa[i] + da[i] * (1.0 - sa[i]);
+                let v = if is_alpha {
+                    a
+                } else if a > 0.0 {
+                    let (s, d): (f32, f32) = (from[sy*src_w + sx].clone().into(), to[at].clone().into());
+                    (s * sa[i] + d * da[i] * (1.0 - sa[i])) / a
+                } else {
+                    0.0
+                };
+                to.write(at, T::from(v));
+            }
+        }
+    }
+
+    /// Sets every pixel of the `w` x `h` rectangle with its top-left corner at (x, y) to `color`.
+    /// Whatever falls outside the image is left out
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: Colora) -> Result<(), ImageFormatError<Self::ChannelName>> {
+        let (x1, y1) = (x.saturating_add(w).min(self.width()), y.saturating_add(h).min(self.height()));
+        for py in y..y1 {
+            for px in x..x1 {
+                self.set_pixel(px, py, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws a line from (x0, y0) to (x1, y1) in `color` with Bresenham's algorithm.
+    /// The line is clipped to the image, keeping its slope
+    fn draw_line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Colora) {
+        let (w, h) = (self.width(), self.height());
+        if w == 0 || h == 0 {
+            return
+        }
+        // Liang-Barsky: narrow the part of the line (0 <= t <= 1) that's inside the image
+        let (fx, fy) = (x0 as f64, y0 as f64);
+        let (dx, dy) = (x1 as f64 - fx, y1 as f64 - fy);
+        let (mut t0, mut t1) = (0.0, 1.0);
+        for &(p, q) in [(-dx, fx), (dx, (w - 1) as f64 - fx), (-dy, fy), (dy, (h - 1) as f64 - fy)].iter() {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return
+                }
+            } else if p < 0.0 {
+                t0 = (q / p).max(t0);
+            } else {
+                t1 = (q / p).min(t1);
+            }
+        }
+        if t0 > t1 {
+            return
+        }
+        let clip = |t: f64, start: f64, d: f64, max: usize| ((start + t * d).round().max(0.0) as usize).min(max - 1) as isize;
+        let (mut x, mut y) = (clip(t0, fx, dx, w), clip(t0, fy, dy, h));
+        let (x1, y1) = (clip(t1, fx, dx, w), clip(t1, fy, dy, h));
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = (if x < x1 { 1 } else { -1 }, if y < y1 { 1 } else { -1 });
+        let mut err = dx + dy;
+        loop {
+            let _ = self.set_pixel(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                break
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draws the outline of the `w` x `h` rectangle with its top-left corner at (x, y) in `color`.
+    /// Whatever falls outside the image is left out
+    fn draw_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: Colora) {
+        if w == 0 || h == 0 {
+            return
+        }
+        let (width, height) = (self.width(), self.height());
+        if x >= width || y >= height {
+            return
+        }
+        let (x1, y1) = (x.saturating_add(w - 1), y.saturating_add(h - 1));
+        for px in x..x1.min(width - 1) + 1 {
+            let _ = self.set_pixel(px, y, color);
+            if y1 < height {
+                let _ = self.set_pixel(px, y1, color);
+            }
+        }
+        for py in y..y1.min(height - 1) + 1 {
+            let _ = self.set_pixel(x, py, color);
+            if x1 < width {
+                let _ = self.set_pixel(x1, py, color);
+            }
+        }
+    }
+
+    /// Sets the region of pixels connected to (x, y) (through their edges) that are within
+    /// `tolerance` of its color, in every component, to `color`
+    fn flood_fill(&mut self, x: usize, y: usize, color: Colora, tolerance: f32) -> Result<(), ImageFormatError<Self::ChannelName>> {
+        let (w, h) = (self.width(), self.height());
+        let components = |c: Colora| -> (f32, f32, f32, f32) { Into::<Rgba>::into(c).to_pixel() };
+        let seed = components(self.pixel(x, y)?);
+        let similar = |c: (f32, f32, f32, f32)| {
+            (c.0 - seed.0).abs() <= tolerance && (c.1 - seed.1).abs() <= tolerance
+                && (c.2 - seed.2).abs() <= tolerance && (c.3 - seed.3).abs() <= tolerance
+        };
+        let mut seen = vec![false; w * h];
+        let mut stack = vec![(x, y)];
+        seen[y*w + x] = true;
+        while let Some((px, py)) = stack.pop() {
+            self.set_pixel(px, py, color)?;
+            let mut next = Vec::with_capacity(4);
+            if px > 0 { next.push((px - 1, py)); }
+            if px + 1 < w { next.push((px + 1, py)); }
+            if py > 0 { next.push((px, py - 1)); }
+            if py + 1 < h { next.push((px, py + 1)); }
+            for (nx, ny) in next {
+                if !seen[ny*w + nx] {
+                    seen[ny*w + nx] = true;
+                    if similar(components(self.pixel(nx, ny)?)) {
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a copy of this image turned 90° clockwise, so width and height are swapped
+    fn rotate90(&self) -> Result<Self, ImageFormatError<Self::ChannelName>> where Self: Sized {
+        let (w, h) = (self.width(), self.height());
+        let mut out = self.with_dimensions(h, w);
+        for name in self.channel_names() {
+            let src = self.channel(&name);
+            if src.len() < w * h {
+                return Err(ImageFormatError::MissingData(name, src.len() % w.max(1), src.len() / w.max(1)))
+            }
+            let dst = out.channel_mut(&name);
+            // (x, y) in the result comes from (y, h - 1 - x) in the source
+            for i in 0..w * h {
+                let (x, y) = (i % h, i / h);
+                dst.write(i, src[(h - 1 - x)*w + y].clone());
+            }
+        }
+        Ok(out)
+    }
+
+    /// Creates a copy of this image turned 90° counter-clockwise, so width and height are swapped
+    fn rotate270(&self) -> Result<Self, ImageFormatError<Self::ChannelName>> where Self: Sized {
+        let (w, h) = (self.width(), self.height());
+        let mut out = self.with_dimensions(h, w);
+        for name in self.channel_names() {
+            let src = self.channel(&name);
+            if src.len() < w * h {
+                return Err(ImageFormatError::MissingData(name, src.len() % w.max(1), src.len() / w.max(1)))
+            }
+            let dst = out.channel_mut(&name);
+            // (x, y) in the result comes from (w - 1 - y, x) in the source
+            for i in 0..w * h {
+                let (x, y) = (i % h, i / h);
+                dst.write(i, src[x*w + w - 1 - y].clone());
+            }
+        }
+        Ok(out)
+    }
+
+    /// Creates a copy of this image turned clockwise by `radians` about its center, keeping the
+    /// same size. Each pixel is blended from the four nearest source pixels, and pixels that come
+    /// from outside the source are set to `background`. Works on the stored values, so visibility
+    /// doesn't matter; channels that don't hold a value for every pixel are left blank
+    fn rotate(&self, radians: f32, background: Colora) -> Self where Self: Sized, T: From<f32> + Into<f32> {
+        let (w, h) = (self.width(), self.height());
+        // Let the format decide what the background means for each channel
+        let mut fill = self.with_dimensions(1, 1);
+        let _ = fill.set_pixel(0, 0, background);
+        let (cx, cy) = ((w as f32 - 1.0) / 2.0, (h as f32 - 1.0) / 2.0);
+        let (sin, cos) = radians.sin_cos();
+        // Allow for rounding error in sin/cos, so exact turns don't lose the edges
+        let eps = 1e-3;
+        resample_channels(self, w, h, |name, src, x, y| {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            let (sx, sy) = (cx + dx*cos + dy*sin, cy - dx*sin + dy*cos);
+            if sx < -eps || sy < -eps || sx > w as f32 - 1.0 + eps || sy > h as f32 - 1.0 + eps {
+                fill.channel(name).get_clone(0).unwrap_or_else(|| T::from(0.0))
+            } else {
+                T::from(bilinear_value(src, w, h, sx, sy))
+            }
+        })
+    }
+
+    /// Adds `delta` to every value of the color channels, clamping to [0, 1]
+    fn adjust_brightness(&mut self, delta: f32) where T: From<f32> + Into<f32> {
+        map_color_values(self, |v| v + delta);
+    }
+
+    /// Scales every value of the color channels away from (or towards) mid-gray
+    /// by `factor`, clamping to [0, 1]
+    fn adjust_contrast(&mut self, factor: f32) where T: From<f32> + Into<f32> {
+        map_color_values(self, |v| (v - 0.5) * factor + 0.5);
+    }
+
+    /// Raises every value of the color channels to the power `1 / gamma`, so
+    /// gammas above 1 brighten the midtones
+    fn apply_gamma(&mut self, gamma: f32) where T: From<f32> + Into<f32> {
+        let exponent = 1.0 / gamma;
+        map_color_values(self, |v| v.max(0.0).powf(exponent));
+    }
+
+    /// Replaces every value of the color channels with its complement, `1 - v`
+    fn invert(&mut self) where T: From<f32> + Into<f32> {
+        map_color_values(self, |v| 1.0 - v);
+    }
+}
+
+/// Replaces every value `v` of the color channels of `img` with `f(v)`, clamped to [0, 1]
+fn map_color_values<T, I, F>(img: &mut I, f: F)
+    where T: Clone + Debug + From<f32> + Into<f32>, I: ImageFormat<T> + ?Sized, F: Fn(f32) -> f32 {
+    for name in img.channel_names() {
+        if img.is_color_channel(&name) {
+            img.channel_mut(&name).apply_indexed(|_, v| {
+                let x: f32 = v.clone().into();
+                *v = T::from(f(x).max(0.0).min(1.0));
+            });
+        }
+    }
+}
+
+/// Creates a `new_w` x `new_h` copy of `img`, setting each value of every channel to
+/// `f(name, values, x, y)`, where `values` are the stored values of that channel. Channels that
+/// don't hold a value for every pixel (in `img` or the copy) are left blank
+fn resample_channels<T, I, F>(img: &I, new_w: usize, new_h: usize, f: F) -> I
+    where T: Clone + Debug, I: ImageFormat<T>, F: Fn(&I::ChannelName, &[T], usize, usize) -> T {
+    let (w, h) = (img.width(), img.height());
+    let mut out = img.with_dimensions(new_w, new_h);
+    if w == 0 || h == 0 {
+        return out
+    }
+    for name in img.channel_names() {
+        let src = img.channel(&name).as_raw();
+        if src.len() < w * h {
+            continue
+        }
+        let values: Vec<T> = (0..new_w * new_h).map(|i| f(&name, src, i % new_w, i / new_w)).collect();
+        let _ = out.channel_mut(&name).write_slice(0, &values);
+    }
+    out
+}
+
+/// Blends the four values around the fractional location (x, y) of `src`, which holds `w` x `h`
+/// values. Locations outside are clamped to its edges
+fn bilinear_value<T: Clone + Into<f32>>(src: &[T], w: usize, h: usize, x: f32, y: f32) -> f32 {
+    let at = |x: usize, y: usize| -> f32 { src[y*w + x].clone().into() };
+    let (x, y) = (x.max(0.0).min(w as f32 - 1.0), y.max(0.0).min(h as f32 - 1.0));
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    (at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx) * (1.0 - fy) + (at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx) * fy
+}
+
+// The Catmull-Rom cubic, weighting a sample `t` pixels away
+fn catmull_rom(t: f32) -> f32 {
+    let t = t.abs();
+    if t < 1.0 {
+        1.5*t*t*t - 2.5*t*t + 1.0
+    } else if t < 2.0 {
+        -0.5*t*t*t + 2.5*t*t - 4.0*t + 2.0
+    } else {
+        0.0
+    }
 }
@@ -1,8 +1,12 @@
-use image::{Channel, Image};
+use image::{Channel, ChannelError, Image};
 use palette::Colora; // Use Colora as a generic color.
 use super::{ImageFormat, ImageFormatError};
+use super::encode::{OutputFormat, EncodeError};
+use super::ycbcr::{YCbCrImage, ChromaSubsampling, YCbCrMatrix};
+use std::io::Write;
 use std::fmt::{Display, Debug, Formatter, Error};
 use std::error::Error as StdError;
+use std::cmp::Ordering;
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 /// Represents the channels of an RGBA image
@@ -17,15 +21,15 @@ pub enum RgbaChannel {
     Alpha
 }
 
-// got lower upper inclusive
+// got lower upper inclusive index
 #[derive(Debug)]
-pub struct InvalidData<T: Debug>(T, T, T, bool);
+pub struct InvalidData<T: Debug>(pub(crate) T, pub(crate) T, pub(crate) T, pub(crate) bool, pub(crate) usize);
 impl<T: Display + Debug> Display for InvalidData<T> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         if self.3 {
-            write!(f, "got {}, expected value in [{}, {}]", self.0, self.1, self.2)
+            write!(f, "got {} at index {}, expected value in [{}, {}]", self.0, self.4, self.1, self.2)
         } else {
-            write!(f, "got {}, expected value in ({}, {})", self.0, self.1, self.2)
+            write!(f, "got {} at index {}, expected value in ({}, {})", self.0, self.4, self.1, self.2)
         }
     }
 }
@@ -34,12 +38,45 @@ impl<T: Display + Debug> StdError for InvalidData<T> {
     fn description(&self) -> &str { "Invalid data" }
 }
 
-/// Stores an RGBA format image
+// Standard deviation of the blur used to build image pyramids
+const PYRAMID_SIGMA: f32 = 1.0;
+
+// Dehazing constants, from He et al. "Single Image Haze Removal Using Dark Channel Prior".
+// The patch radius of the dark channel, how much haze is removed at full strength, and the
+// smallest transmission allowed (so dense haze doesn't blow up into noise)
+const DEHAZE_RADIUS: usize = 7;
+const DEHAZE_OMEGA: f32 = 0.95;
+const DEHAZE_MIN_TRANSMISSION: f32 = 0.1;
+
+/// An RGBA image as four separate planes of 8-bit values
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawPlanar {
+    /// The red values, row by row
+    pub r: Vec<u8>,
+    /// The green values, row by row
+    pub g: Vec<u8>,
+    /// The blue values, row by row
+    pub b: Vec<u8>,
+    /// The alpha values, row by row
+    pub a: Vec<u8>,
+    /// The number of values in a row
+    pub width: usize,
+    /// The number of rows
+    pub height: usize,
+}
+
+impl RawPlanar {
+    // Every plane, with the channel it holds
+    fn planes(&self) -> [(RgbaChannel, &Vec<u8>); 4] {
+        [(RgbaChannel::Red, &self.r), (RgbaChannel::Green, &self.g), (RgbaChannel::Blue, &self.b), (RgbaChannel::Alpha, &self.a)]
+    }
+}
+
+/// Stores an RGBA format image. It can be sent and shared between threads
+#[derive(Clone, Debug)]
 pub struct RgbaImage {
     image: Image<f32>,
     channels: [bool; 4],
-    width: usize,
-    height: usize
 }
 
 macro_rules! channel {
@@ -72,16 +109,9 @@ macro_rules! get_channel {
 impl RgbaImage {
     /// Creates a new RgbaImage
     pub fn new(w: usize, h: usize) -> RgbaImage {
-        let mut i = Image::new(w * h);
-        i.create_channel(0.0);
-        i.create_channel(0.0);
-        i.create_channel(0.0);
-        i.create_channel(1.0);
         RgbaImage {
-            image: i,
+            image: Image::with_channels(w * h, &[0.0, 0.0, 0.0, 1.0]).reshaped(w, h),
             channels: [false; 4],
-            width: w,
-            height: h
         }
     }
 
@@ -94,6 +124,408 @@ impl RgbaImage {
         }
     }
 
+    /// Replaces the data of channel `c` (by index), keeping its label and counting every value
+    /// as written
+    fn set_plane(&mut self, c: usize, data: Vec<f32>) {
+        self.image[c].copy_from_slice(&data).expect("RgbaImage internal error: plane has the wrong size");
+    }
+
+    /// Samples every channel (visible or not) at the fractional location (x, y), blending the four
+    /// surrounding pixels. Locations outside the image are clamped to its edges
+    fn sample_bilinear(&self, x: f32, y: f32) -> Colora {
+        let (w, h) = (self.image.width(), self.image.height());
+        let x = x.max(0.0).min((w - 1) as f32);
+        let y = y.max(0.0).min((h - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let at = |c: usize, x: usize, y: usize| *self.image.get_2d(c, x, y).expect("RgbaImage internal error: sample outside image");
+        let sample = |c: usize| {
+            let top = at(c, x0, y0) * (1.0 - fx) + at(c, x1, y0) * fx;
+            let bottom = at(c, x0, y1) * (1.0 - fx) + at(c, x1, y1) * fx;
+            top * (1.0 - fy) + bottom * fy
+        };
+        Colora::rgb(sample(0), sample(1), sample(2), sample(3))
+    }
+
+    /// Takes `samples` equally spaced bilinear samples on the line from (x0, y0) to (x1, y1),
+    /// including both end points
+    pub fn sample_along_line(&self, x0: f32, y0: f32, x1: f32, y1: f32, samples: usize) -> Vec<Colora> {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return vec![]
+        }
+        (0..samples).map(|i| {
+            let t = if samples > 1 { i as f32 / (samples - 1) as f32 } else { 0.0 };
+            self.sample_bilinear(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+        }).collect()
+    }
+
+    /// Rotates the hue of pixels whose hue is within `range` degrees of `center_hue` by
+    /// `rotation` degrees. The rotation fades out smoothly (along a cosine) towards the
+    /// edges of the range. Gray pixels have no hue, and are left alone
+    pub fn rotate_hue_in_range(&self, center_hue: f32, range: f32, rotation: f32) -> RgbaImage {
+        use std::f32::consts::PI;
+
+        let mut image = self.clone();
+        for i in 0..self.image.len() {
+            let (h, s, l) = rgb_to_hsl(self.red()[i], self.green()[i], self.blue()[i]);
+            if s == 0.0 {
+                continue
+            }
+            // Shortest distance around the color wheel
+            let distance = ((h - center_hue) % 360.0 + 540.0) % 360.0 - 180.0;
+            let distance = distance.abs();
+            if distance >= range {
+                continue
+            }
+            let weight = 0.5 * (1.0 + (PI * distance / range).cos());
+            let (r, g, b) = hsl_to_rgb(h + rotation * weight, s, l);
+            image.red_mut().write(i, r);
+            image.green_mut().write(i, g);
+            image.blue_mut().write(i, b);
+        }
+        image
+    }
+
+    /// Call `f` with the (x, y) location and a mutable color for every pixel, storing the
+    /// color afterwards. Pixels are visited in storage order (row by row)
+    pub fn for_each_pixel_mut<F: FnMut(usize, usize, &mut Colora)>(&mut self, mut f: F) {
+        use palette::Rgba;
+
+        for i in 0..self.image.len() {
+            let (x, y) = (i % self.image.width(), i / self.image.width());
+            let mut color = Colora::rgb(self.red()[i], self.green()[i], self.blue()[i], self.alpha()[i]);
+            f(x, y, &mut color);
+            let (r, g, b, a) = Into::<Rgba>::into(color).to_pixel();
+            self.red_mut().write(i, r);
+            self.green_mut().write(i, g);
+            self.blue_mut().write(i, b);
+            self.alpha_mut().write(i, a);
+        }
+    }
+
+    /// Creates a `w` by `h` thumbnail by taking evenly strided pixels from evenly spaced rows.
+    /// The size is clamped to the size of this image, as this can only shrink
+    pub fn thumbnail_nearest(&self, w: usize, h: usize) -> RgbaImage {
+        let (w, h) = (w.min(self.image.width()), h.min(self.image.height()));
+        let mut thumb = RgbaImage::new(w, h);
+        thumb.channels = self.channels;
+        if w == 0 || h == 0 {
+            return thumb
+        }
+        let step = self.image.width() / w;
+        for y in 0..h {
+            let row = self.image.crop(0, y * self.image.height() / h, self.image.width(), 1)
+                .expect("RgbaImage internal error: row outside image");
+            for c in 0..4 {
+                let samples = row[c].stride_sample(step, 0).expect("RgbaImage internal error: zero step");
+                for (x, v) in samples.iter().take(w).enumerate() {
+                    thumb.image.set_2d(c, x, y, *v).expect("RgbaImage internal error: thumbnail pixel outside image");
+                }
+            }
+        }
+        thumb
+    }
+
+    /// Decodes the color channels from sRGB into linear light. Alpha is left alone
+    pub fn to_linear(&mut self) {
+        for c in 0..3 {
+            self.image[c].to_linear();
+        }
+    }
+
+    /// Encodes the color channels from linear light into sRGB. Alpha is left alone
+    pub fn to_srgb(&mut self) {
+        for c in 0..3 {
+            self.image[c].to_srgb();
+        }
+    }
+
+    /// Reduces each color channel to `levels` evenly spaced values. Alpha is left alone
+    pub fn posterize(&mut self, levels: usize) -> Result<(), ChannelError> {
+        for c in 0..3 {
+            self.image[c].quantize(levels)?;
+        }
+        Ok(())
+    }
+
+    /// Creates a copy with the color channels scaled by `stops` doublings of light, clamped to
+    /// [0, 1]. Negative stops darken. Alpha is left alone
+    pub fn exposure_adjust(&self, stops: f32) -> RgbaImage {
+        let mut image = self.clone();
+        let scale = 2.0f32.powf(stops);
+        for c in 0..3 {
+            image.image[c].apply_indexed(|_, v| *v = (*v * scale).max(0.0).min(1.0));
+        }
+        image
+    }
+
+    /// Creates a copy with a tone curve applied to the color channels, raising dark values by up
+    /// to `shadow_lift` (at 1/3) and lowering bright ones by up to `highlight_roll` (at 2/3).
+    /// Black and white stay put, and alpha is left alone
+    pub fn shadows_highlights(&self, shadow_lift: f32, highlight_roll: f32) -> RgbaImage {
+        let mut image = self.clone();
+        // v(1-v)^2 and v^2(1-v) peak at 4/27, so scale them to peak at 1
+        let curve = |v: f32| {
+            let v = v.max(0.0).min(1.0);
+            let shadows = 6.75 * v * (1.0 - v) * (1.0 - v);
+            let highlights = 6.75 * v * v * (1.0 - v);
+            (v + shadow_lift * shadows - highlight_roll * highlights).max(0.0).min(1.0)
+        };
+        for c in 0..3 {
+            image.image[c].apply_indexed(|_, v| *v = curve(*v));
+        }
+        image
+    }
+
+    /// Replaces the color channels with the (BT.709) luminance of each pixel. Alpha is left alone
+    pub fn to_grayscale(&mut self) {
+        let luma: Vec<f32> = self.red().iter().zip(self.green().iter()).zip(self.blue().iter())
+            .map(|((r, g), b)| 0.2126 * r + 0.7152 * g + 0.0722 * b)
+            .collect();
+        for c in 0..3 {
+            self.image[c].copy_from_slice(&luma).expect("RgbaImage internal error: channels have different lengths");
+        }
+    }
+
+    /// Tints the color channels brown with the usual sepia matrix, clamping to [0, 1].
+    /// Alpha is left alone
+    pub fn sepia(&mut self) {
+        const SEPIA: [[f32; 3]; 3] = [
+            [0.393, 0.769, 0.189],
+            [0.349, 0.686, 0.168],
+            [0.272, 0.534, 0.131],
+        ];
+        let (r, g, b) = (self.red().to_vec(), self.green().to_vec(), self.blue().to_vec());
+        for (c, row) in SEPIA.iter().enumerate() {
+            self.image[c].apply_indexed(|i, v| {
+                *v = (row[0] * r[i] + row[1] * g[i] + row[2] * b[i]).max(0.0).min(1.0)
+            });
+        }
+    }
+
+    /// Sets every pixel to `color`
+    pub fn fill(&mut self, color: Colora) {
+        use palette::Rgba;
+
+        let (r, g, b, a): (f32, f32, f32, f32) = Into::<Rgba>::into(color).to_pixel();
+        self.image.fill_all(&[r, g, b, a]).expect("RgbaImage internal error: wrong channel count");
+    }
+
+    /// Blurs every channel with a gaussian kernel of standard deviation `sigma`.
+    /// Pixels past the edges are treated as copies of the nearest edge pixel
+    pub fn gaussian_blur(&self, sigma: f32) -> RgbaImage {
+        let mut image = self.clone();
+        if sigma <= 0.0 || self.image.width() == 0 || self.image.height() == 0 {
+            return image
+        }
+        let radius = (3.0 * sigma).ceil() as isize;
+        let mut kernel: Vec<f32> = (-radius..radius+1).map(|i| (-(i*i) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+        let total: f32 = kernel.iter().sum();
+        for k in kernel.iter_mut() {
+            *k /= total;
+        }
+
+        let (w, h) = (self.image.width() as isize, self.image.height() as isize);
+        for c in 0..4 {
+            // The kernel is separable, so blur the rows, then the columns
+            let src = &self.image[c];
+            let mut rows = vec![0.0; src.len()];
+            for y in 0..h {
+                for x in 0..w {
+                    rows[(y*w + x) as usize] = kernel.iter().enumerate().map(|(k, weight)| {
+                        let sx = (x + k as isize - radius).max(0).min(w - 1);
+                        src[(y*w + sx) as usize] * weight
+                    }).sum();
+                }
+            }
+            let mut cols = vec![0.0; src.len()];
+            for y in 0..h {
+                for x in 0..w {
+                    cols[(y*w + x) as usize] = kernel.iter().enumerate().map(|(k, weight)| {
+                        let sy = (y + k as isize - radius).max(0).min(h - 1);
+                        rows[(sy*w + x) as usize] * weight
+                    }).sum();
+                }
+            }
+            image.set_plane(c, cols);
+        }
+        image
+    }
+
+    /// Denoises the color channels with Non-Local Means: every pixel becomes a weighted average
+    /// of the pixels in the `search_window` sized square around it, where pixels whose
+    /// surrounding `patch_size` sized square looks similar get more weight.
+    /// `h` controls how quickly the weight falls off with patch difference. Alpha is left alone
+    pub fn non_local_means(&self, h: f32, patch_size: usize, search_window: usize) -> RgbaImage {
+        let mut image = self.clone();
+        if h <= 0.0 || self.image.width() == 0 || self.image.height() == 0 {
+            return image
+        }
+        let (w, ht) = (self.image.width() as isize, self.image.height() as isize);
+        let (pr, sr) = ((patch_size / 2) as isize, (search_window / 2) as isize);
+        let at = |x: isize, y: isize| (y.max(0).min(ht - 1) * w + x.max(0).min(w - 1)) as usize;
+        let patch_area = ((2 * pr + 1) * (2 * pr + 1) * 3) as f32;
+
+        let mut planes = vec![vec![0.0; self.image.len()]; 3];
+        for py in 0..ht {
+            for px in 0..w {
+                let mut total = 0.0;
+                let mut sums = [0.0f32; 3];
+                for qy in (py - sr).max(0)..(py + sr + 1).min(ht) {
+                    for qx in (px - sr).max(0)..(px + sr + 1).min(w) {
+                        // Mean squared difference between the patches around p and q
+                        let mut distance = 0.0;
+                        for oy in -pr..pr+1 {
+                            for ox in -pr..pr+1 {
+                                let (a, b) = (at(px + ox, py + oy), at(qx + ox, qy + oy));
+                                for c in 0..3 {
+                                    let d = self.image[c][a] - self.image[c][b];
+                                    distance += d * d;
+                                }
+                            }
+                        }
+                        let weight = (-(distance / patch_area) / (h * h)).exp();
+                        let q = at(qx, qy);
+                        for c in 0..3 {
+                            sums[c] += weight * self.image[c][q];
+                        }
+                        total += weight;
+                    }
+                }
+                let p = at(px, py);
+                for c in 0..3 {
+                    planes[c][p] = sums[c] / total;
+                }
+            }
+        }
+        for (c, plane) in planes.into_iter().enumerate() {
+            image.set_plane(c, plane);
+        }
+        image
+    }
+
+    /// Removes haze using the dark channel prior. `amount` goes from 0 (no change) to 1 (full
+    /// strength). Alpha is left alone
+    pub fn dehaze(&self, amount: f32) -> RgbaImage {
+        let mut image = self.clone();
+        let (w, h) = (self.image.width(), self.image.height());
+        if amount <= 0.0 || w == 0 || h == 0 {
+            return image
+        }
+        let len = w * h;
+        let darkest = |i: usize, scale: &[f32; 3]| (0..3).map(|c| self.image[c][i] / scale[c]).fold(::std::f32::INFINITY, |a, b| a.min(b));
+
+        // The atmospheric light is the brightest of the haziest 0.1% of pixels (by dark channel)
+        let dark = min_filter(&(0..len).map(|i| darkest(i, &[1.0; 3])).collect::<Vec<_>>(), w, h, DEHAZE_RADIUS);
+        let mut sorted = dark.clone();
+        sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let cutoff = sorted[(len / 1000).max(1) - 1];
+        let brightness = |i: usize| self.image[0][i] + self.image[1][i] + self.image[2][i];
+        let top = (0..len).filter(|&i| dark[i] >= cutoff)
+            .fold(None, |best: Option<usize>, i| match best {
+                Some(b) if brightness(b) >= brightness(i) => Some(b),
+                _ => Some(i)
+            }).unwrap_or(0);
+        let mut light = [0.0; 3];
+        for c in 0..3 {
+            light[c] = self.image[c][top].max(1e-3);
+        }
+
+        // Invert the haze model I = J * t + A * (1 - t)
+        let haze = min_filter(&(0..len).map(|i| darkest(i, &light)).collect::<Vec<_>>(), w, h, DEHAZE_RADIUS);
+        let strength = DEHAZE_OMEGA * amount.min(1.0);
+        for c in 0..3 {
+            let a = light[c];
+            image.image[c].apply_indexed(|i, v| {
+                let t = (1.0 - strength * haze[i]).max(DEHAZE_MIN_TRANSMISSION);
+                *v = ((*v - a) / t + a).max(0.0).min(1.0);
+            });
+        }
+        image
+    }
+
+    /// Halves the size by blurring, then dropping every other row and column
+    fn pyramid_down(&self) -> RgbaImage {
+        let blurred = self.gaussian_blur(PYRAMID_SIGMA);
+        let (w, h) = ((self.image.width() + 1) / 2, (self.image.height() + 1) / 2);
+        let mut small = RgbaImage::new(w, h);
+        small.channels = self.channels;
+        for c in 0..4 {
+            let plane = (0..w*h).map(|i| {
+                *blurred.image.get_2d(c, (i % w) * 2, (i / w) * 2).expect("RgbaImage internal error: sample outside image")
+            }).collect();
+            small.set_plane(c, plane);
+        }
+        small
+    }
+
+    /// Doubles the size (cut down to `w` by `h`) by inserting zeros between pixels, then blurring
+    fn pyramid_up(&self, w: usize, h: usize) -> RgbaImage {
+        let mut big = RgbaImage::new(w, h);
+        big.channels = self.channels;
+        for c in 0..4 {
+            // Every row gets zeros between its values, and every other row is all zeros
+            let mut plane = Vec::with_capacity(w * h);
+            for y in 0..h {
+                for x in 0..w {
+                    plane.push(if y % 2 == 0 && x % 2 == 0 {
+                        *self.image.get_2d(c, x / 2, y / 2).expect("RgbaImage internal error: sample outside image")
+                    } else {
+                        0.0
+                    });
+                }
+            }
+            big.set_plane(c, plane);
+        }
+        let mut big = big.gaussian_blur(PYRAMID_SIGMA);
+        // Only a quarter of the pixels had values, so make up for the lost brightness
+        for c in 0..4 {
+            big.image[c].apply_indexed(|_, v| *v *= 4.0);
+        }
+        big
+    }
+
+    /// Splits the image into `levels` images: the details lost at each halving of the size,
+    /// followed by the smallest (blurred) image. The detail images can hold negative values
+    pub fn laplacian_pyramid(&self, levels: usize) -> Vec<RgbaImage> {
+        let mut pyramid = Vec::with_capacity(levels);
+        if levels == 0 {
+            return pyramid
+        }
+        let mut current = self.clone();
+        for _ in 1..levels {
+            let down = current.pyramid_down();
+            let up = down.pyramid_up(current.image.width(), current.image.height());
+            let mut detail = current;
+            for c in 0..4 {
+                let u = &up.image[c];
+                detail.image[c].apply_indexed(|i, v| *v -= u[i]);
+            }
+            pyramid.push(detail);
+            current = down;
+        }
+        pyramid.push(current);
+        pyramid
+    }
+
+    /// Rebuilds the image split up by `laplacian_pyramid`
+    pub fn reconstruct_from_laplacian_pyramid(pyramid: &[RgbaImage]) -> RgbaImage {
+        let mut image = match pyramid.last() {
+            Some(smallest) => smallest.clone(),
+            None => return RgbaImage::new(0, 0)
+        };
+        for detail in pyramid.iter().rev().skip(1) {
+            let mut up = image.pyramid_up(detail.image.width(), detail.image.height());
+            for c in 0..4 {
+                let d = &detail.image[c];
+                up.image[c].apply_indexed(|i, v| *v += d[i]);
+            }
+            image = up;
+        }
+        image
+    }
+
     get_channel!(red, is_red_visible with RgbaChannel::Red => RgbaImage);
     get_channel!(green, is_green_visible with RgbaChannel::Green => RgbaImage);
     get_channel!(blue, is_blue_visible with RgbaChannel::Blue => RgbaImage);
@@ -107,6 +539,190 @@ impl RgbaImage {
     channel!(RgbaImage, mutable blue using RgbaChannel::Blue as blue_mut);
     channel!(RgbaImage, alpha using RgbaChannel::Alpha);
     channel!(RgbaImage, mutable alpha using RgbaChannel::Alpha as alpha_mut);
+
+    /// Checks that `other` has the same size, and that no value in any channel (visible or not)
+    /// differs from it by `tolerance` or more
+    pub fn close_to(&self, other: &RgbaImage, tolerance: f32) -> bool {
+        if self.image.width() != other.image.width() || self.image.height() != other.image.height() {
+            return false
+        }
+        (0..4).all(|c| {
+            self.image[c].iter().zip(other.image[c].iter()).all(|(a, b)| (a - b).abs() < tolerance)
+        })
+    }
+
+    /// Converts every channel (visible or not) to 8-bit values, giving the R, G, B and A planes
+    /// along with the width and height
+    pub fn to_rgba_u8_planes(&self) -> ([Vec<u8>; 4], usize, usize) {
+        let planes = [
+            self.red().to_u8().into_vec(),
+            self.green().to_u8().into_vec(),
+            self.blue().to_u8().into_vec(),
+            self.alpha().to_u8().into_vec(),
+        ];
+        (planes, self.image.width(), self.image.height())
+    }
+
+    /// Converts every channel (visible or not) to 8-bit planes
+    pub fn encode_raw_planar(&self) -> RawPlanar {
+        let ([r, g, b, a], width, height) = self.to_rgba_u8_planes();
+        RawPlanar { r: r, g: g, b: b, a: a, width: width, height: height }
+    }
+
+    /// Creates an image from 8-bit planes. Panics if a plane isn't `width * height` long
+    pub fn decode_raw_planar(raw: &RawPlanar) -> RgbaImage {
+        let len = raw.width * raw.height;
+        for &(name, plane) in raw.planes().iter() {
+            assert!(plane.len() == len, "RawPlanar {:?} plane has {} values, but a {}x{} image needs {}",
+                name, plane.len(), raw.width, raw.height, len);
+        }
+        RgbaImage::try_decode_raw_planar(raw).expect("RgbaImage internal error: planes were checked")
+    }
+
+    /// Creates an image from 8-bit planes. A plane that's too short gives `MissingData` at its
+    /// first missing pixel, and one that's too long gives a `LengthMismatch`
+    pub fn try_decode_raw_planar(raw: &RawPlanar) -> Result<RgbaImage, RgbaImageError> {
+        let len = raw.width * raw.height;
+        for &(name, plane) in raw.planes().iter() {
+            if plane.len() < len {
+                return Err(ImageFormatError::MissingData(name, plane.len() % raw.width, plane.len() / raw.width))
+            } else if plane.len() > len {
+                return Err(ChannelError::LengthMismatch { expected: len, got: plane.len() }.into())
+            }
+        }
+        let mut image = RgbaImage::new(raw.width, raw.height);
+        for (c, &(_, plane)) in raw.planes().iter().enumerate() {
+            image.set_plane(c, Channel::from_u8_scaled(plane).into_vec());
+        }
+        Ok(image)
+    }
+
+    /// Converts the color channels to YCbCr with `matrix`, averaging chroma over the pixels
+    /// that share it. Alpha is dropped. The YCbCr channels are visible if all of R, G and B are
+    pub fn to_ycbcr(&self, subsampling: ChromaSubsampling, matrix: YCbCrMatrix) -> YCbCrImage {
+        let (w, h) = (self.image.width(), self.image.height());
+        let mut image = YCbCrImage::with_matrix(w, h, subsampling, matrix);
+        let (fx, fy) = subsampling.factors();
+        let cw = image.chroma_width();
+        let chroma_len = cw * image.chroma_height();
+        let (mut luma, mut cb, mut cr) = (vec![0.0; w * h], vec![0.0; chroma_len], vec![0.0; chroma_len]);
+        let mut shared = vec![0.0; chroma_len];
+        for i in 0..w * h {
+            let (y, b, r) = matrix.rgb_to_ycbcr(self.red()[i], self.green()[i], self.blue()[i]);
+            let at = (i / w / fy) * cw + (i % w) / fx;
+            luma[i] = y;
+            cb[at] += b;
+            cr[at] += r;
+            shared[at] += 1.0;
+        }
+        for at in 0..chroma_len {
+            cb[at] /= shared[at];
+            cr[at] /= shared[at];
+        }
+
+        let visible = self.channels[..3].iter().all(|v| *v);
+        for (name, plane) in image.channel_names().into_iter().zip([luma, cb, cr].iter()) {
+            image.channel_mut(&name).copy_from_slice(plane).expect("RgbaImage internal error: plane has the wrong size");
+            image.set_channel_visible(&name, visible);
+        }
+        image
+    }
+
+    /// Writes this image to `writer` in the given file format
+    pub fn encode<W: Write>(&self, writer: &mut W, format: OutputFormat) -> Result<(), EncodeError> {
+        format.encoder().encode(self, writer)
+    }
+}
+
+/// Computes the Pearson correlation between every pair of channels (in R, G, B, A order)
+/// over all pixels. Channels that don't vary have no defined correlation, so they get 0
+/// (except with themselves, which is always 1)
+pub fn channel_correlation_matrix(img: &RgbaImage) -> [[f32; 4]; 4] {
+    let n = img.image.len() as f64;
+    let mut means = [0.0f64; 4];
+    for c in 0..4 {
+        means[c] = img.image[c].iter().map(|v| *v as f64).sum::<f64>() / n;
+    }
+    let mut cov = [[0.0f64; 4]; 4];
+    for i in 0..img.image.len() {
+        for a in 0..4 {
+            for b in a..4 {
+                cov[a][b] += (img.image[a][i] as f64 - means[a]) * (img.image[b][i] as f64 - means[b]);
+            }
+        }
+    }
+    let mut matrix = [[0.0f32; 4]; 4];
+    for a in 0..4 {
+        for b in a..4 {
+            let r = if a == b {
+                1.0
+            } else if cov[a][a] > 0.0 && cov[b][b] > 0.0 {
+                (cov[a][b] / (cov[a][a] * cov[b][b]).sqrt()) as f32
+            } else {
+                0.0
+            };
+            matrix[a][b] = r;
+            matrix[b][a] = r;
+        }
+    }
+    matrix
+}
+
+// Replaces each value of a w x h plane with the smallest value in the square of `radius` around it
+fn min_filter(values: &[f32], w: usize, h: usize, radius: usize) -> Vec<f32> {
+    // Separable, so take the minimum along rows, then along columns
+    let mut rows = vec![0.0; values.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let (lo, hi) = (x.saturating_sub(radius), (x + radius + 1).min(w));
+            rows[y * w + x] = values[y * w + lo..y * w + hi].iter().fold(::std::f32::INFINITY, |a, b| a.min(*b));
+        }
+    }
+    let mut out = vec![0.0; values.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let (lo, hi) = (y.saturating_sub(radius), (y + radius + 1).min(h));
+            out[y * w + x] = (lo..hi).map(|yy| rows[yy * w + x]).fold(::std::f32::INFINITY, |a, b| a.min(b));
+        }
+    }
+    out
+}
+
+// Converts RGB to (hue in degrees [0, 360), saturation, lightness)
+fn rgb_to_hsl(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l)
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * (((g - b) / d) % 6.0)
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    ((h + 360.0) % 360.0, s, l)
+}
+
+// Converts (hue in degrees, saturation, lightness) back to RGB
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
+    let h = ((h % 360.0) + 360.0) % 360.0;
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (r + m, g + m, b + m)
 }
 
 /// Errors for RGBA images
@@ -123,12 +739,21 @@ impl ImageFormat<f32> for RgbaImage {
     type ValidationError = InvalidData<f32>;
 
     fn channel_count(&self) -> usize { self.image.count() }
+    fn channel_names(&self) -> Vec<RgbaChannel> {
+        vec![RgbaChannel::Red, RgbaChannel::Green, RgbaChannel::Blue, RgbaChannel::Alpha]
+    }
     fn set_channel_visible(&mut self, c: &RgbaChannel, enabled: bool) {
         self.channels[RgbaImage::to_channel(c)] = enabled;
     }
     fn is_channel_visible(&self, c: &RgbaChannel) -> bool {
         self.channels[RgbaImage::to_channel(c)]
     }
+    fn is_color_channel(&self, c: &RgbaChannel) -> bool {
+        *c != RgbaChannel::Alpha
+    }
+    fn is_alpha_channel(&self, c: &RgbaChannel) -> bool {
+        *c == RgbaChannel::Alpha
+    }
     fn channel(&self, c: &RgbaChannel) -> &Channel<f32> {
         self.image.channel(RgbaImage::to_channel(c)).expect("RgbaImage internal error: missing channel")
     }
@@ -136,24 +761,25 @@ impl ImageFormat<f32> for RgbaImage {
         self.image.channel_mut(RgbaImage::to_channel(c)).expect("RgbaImage internal error: missing channel")
     }
 
-    fn width(&self) -> usize { self.width }
-    fn height(&self) -> usize { self.height }
+    fn width(&self) -> usize { self.image.width() }
+    fn height(&self) -> usize { self.image.height() }
+    fn with_dimensions(&self, w: usize, h: usize) -> RgbaImage {
+        let mut image = RgbaImage::new(w, h);
+        image.channels = self.channels;
+        image
+    }
 
     fn validate(&self) -> Result<(), Self::ValidationError> {
-        for i in 0..self.image.count() {
-            let v = self.image.channel(i).unwrap().iter().find(|x| **x > 1.0 || **x < 0.0);
-            if let Some(v) = v {
-                return Err(InvalidData(*v, 0.0, 1.0, true));
+        for c in self.image.channels() {
+            if let Some(at) = c.position(|x| *x > 1.0 || *x < 0.0) {
+                return Err(InvalidData(c[at], 0.0, 1.0, true, at));
             }
         }
         Ok(())
     }
 
     fn pixel(&self, x: usize, y: usize) -> Result<Colora, RgbaImageError> {
-        if x >= self.width() || y >= self.height() {
-            return Err(ImageFormatError::OutOfBounds(x, y))
-        }
-        let loc = y*self.width() + x;
+        let loc = self.image.index_of(x, y).ok_or(ImageFormatError::OutOfBounds(x, y))?;
         let r = if self.is_red_visible() {
             *self.red().get(loc).ok_or(ImageFormatError::MissingData(RgbaChannel::Red, x, y))?
         } else {
@@ -180,10 +806,7 @@ impl ImageFormat<f32> for RgbaImage {
     fn set_pixel(&mut self, x: usize, y: usize, c: Colora) -> Result<(), RgbaImageError> {
         use palette::Rgba;
 
-        if x >= self.width() || y >= self.height() {
-            return Err(ImageFormatError::OutOfBounds(x, y))
-        }
-        let loc = y*self.width() + x;
+        let loc = self.image.index_of(x, y).ok_or(ImageFormatError::OutOfBounds(x, y))?;
         let (r, g, b, a) = Into::<Rgba>::into(c).to_pixel();
         self.red_mut().get_mut(loc).map(|x| *x = r).ok_or(ImageFormatError::MissingData(RgbaChannel::Red, x, y))?;
         self.green_mut().get_mut(loc).map(|x| *x = g).ok_or(ImageFormatError::MissingData(RgbaChannel::Green, x, y))?;
@@ -205,8 +828,8 @@ impl ImageFormat<f32> for RgbaImage {
 
 #[cfg(test)]
 mod tests {
-    use super::{RgbaImage, ImageFormat};
-    use palette::Rgba;
+    use super::{RgbaImage, RgbaChannel, ImageFormat, channel_correlation_matrix};
+    use palette::{Colora, Rgba};
 
     #[test]
     fn rgbaimage_creation() {
@@ -220,4 +843,908 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn rgbaimage_sample_along_line() {
+        let mut image = RgbaImage::new(3, 1);
+        image.set_pixel(0, 0, Colora::rgb(0.0, 0.0, 0.0, 1.0)).unwrap();
+        image.set_pixel(1, 0, Colora::rgb(1.0, 0.5, 0.0, 1.0)).unwrap();
+        image.set_pixel(2, 0, Colora::rgb(0.0, 1.0, 0.0, 0.0)).unwrap();
+        let samples: Vec<(f32, f32, f32, f32)> = image.sample_along_line(0.0, 0.0, 2.0, 0.0, 5).into_iter()
+            .map(|c| Into::<Rgba>::into(c).to_pixel()).collect();
+        assert_eq!(samples, vec![
+            (0.0, 0.0, 0.0, 1.0),
+            (0.5, 0.25, 0.0, 1.0),
+            (1.0, 0.5, 0.0, 1.0),
+            (0.5, 0.75, 0.0, 0.5),
+            (0.0, 1.0, 0.0, 0.0),
+        ]);
+        assert!(image.sample_along_line(0.0, 0.0, 2.0, 0.0, 0).is_empty());
+    }
+
+    #[test]
+    fn rgbaimage_rotate_hue_in_range() {
+        let mut image = RgbaImage::new(4, 1);
+        image.set_pixel(0, 0, Colora::rgb(1.0, 0.0, 0.0, 1.0)).unwrap(); // hue 0
+        image.set_pixel(1, 0, Colora::rgb(1.0, 0.25, 0.0, 1.0)).unwrap(); // hue 15
+        image.set_pixel(2, 0, Colora::rgb(0.0, 0.0, 1.0, 1.0)).unwrap(); // hue 240
+        image.set_pixel(3, 0, Colora::rgb(0.5, 0.5, 0.5, 1.0)).unwrap(); // gray
+        let rotated = image.rotate_hue_in_range(0.0, 30.0, 120.0);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        // Red sits at the center, so it gets the full rotation to green
+        assert!(close(rotated.red()[0], 0.0) && close(rotated.green()[0], 1.0) && close(rotated.blue()[0], 0.0));
+        // Halfway to the edge, so it's rotated by 60 degrees to hue 75
+        assert!(close(rotated.red()[1], 0.75) && close(rotated.green()[1], 1.0) && close(rotated.blue()[1], 0.0));
+        // Out of range, and hueless pixels are untouched
+        assert_eq!((rotated.red()[2], rotated.green()[2], rotated.blue()[2]), (0.0, 0.0, 1.0));
+        assert_eq!((rotated.red()[3], rotated.green()[3], rotated.blue()[3]), (0.5, 0.5, 0.5));
+        assert_eq!(rotated.alpha().to_vec(), image.alpha().to_vec());
+    }
+
+    #[test]
+    fn rgbaimage_for_each_pixel_mut() {
+        let mut image = RgbaImage::new(3, 2);
+        let mut visited = vec![];
+        image.for_each_pixel_mut(|x, y, c| {
+            visited.push((x, y));
+            *c = Colora::rgb(x as f32 / 10.0, y as f32 / 10.0, 0.0, 1.0);
+        });
+        assert_eq!(visited, vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+        // Pixel (x, y) lives at index y*width + x
+        assert_eq!(image.red()[5], 0.2);
+        assert_eq!(image.green()[5], 0.1);
+        assert_eq!(image.red()[1], 0.1);
+        assert_eq!(image.green()[1], 0.0);
+    }
+
+    #[test]
+    fn rgbaimage_thumbnail_nearest() {
+        let mut image = RgbaImage::new(10, 6);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 10.0, y as f32 / 10.0, 0.0, 1.0));
+        let thumb = image.thumbnail_nearest(3, 2);
+        assert_eq!((thumb.width(), thumb.height()), (3, 2));
+        assert_eq!(thumb.red().len(), 6);
+        // Columns 0, 3, 6 of rows 0 and 3
+        assert_eq!(thumb.red().to_vec(), vec![0.0, 0.3, 0.6, 0.0, 0.3, 0.6]);
+        assert_eq!(thumb.green().to_vec(), vec![0.0, 0.0, 0.0, 0.3, 0.3, 0.3]);
+        let same = image.thumbnail_nearest(10, 6);
+        assert_eq!(same.red().to_vec(), image.red().to_vec());
+        assert_eq!((image.thumbnail_nearest(20, 0).width(), image.thumbnail_nearest(20, 0).height()), (10, 0));
+    }
+
+    #[test]
+    fn rgbaimage_gaussian_blur() {
+        let mut image = RgbaImage::new(5, 5);
+        image.for_each_pixel_mut(|_, _, c| *c = Colora::rgb(0.25, 0.5, 0.75, 1.0));
+        let blurred = image.gaussian_blur(1.5);
+        assert!(blurred.red().iter().all(|v| (v - 0.25).abs() < 1e-5));
+        assert!(blurred.blue().iter().all(|v| (v - 0.75).abs() < 1e-5));
+
+        image.set_pixel(2, 2, Colora::rgb(1.0, 0.5, 0.75, 1.0)).unwrap();
+        let blurred = image.gaussian_blur(1.0);
+        // The bright spot spreads out to its neighbours
+        assert!(blurred.red()[12] < 1.0 && blurred.red()[12] > blurred.red()[11]);
+        assert!(blurred.red()[11] > 0.25);
+    }
+
+    #[test]
+    fn rgbaimage_filters_keep_channel_state() {
+        let mut image = RgbaImage::new(4, 4);
+        image.image.set_channel_label(0, "base").unwrap();
+        image.red_mut().write(5, 1.0);
+        let mut blurred = image.gaussian_blur(1.0);
+        assert_eq!(blurred.red().label(), Some("base"));
+        // The pending write is still there, and the filter counts as writing everything
+        assert_eq!(blurred.red_mut().take_dirty(), Some(0..16));
+        assert_eq!(blurred.image.take_dirty(), Some(0..16));
+    }
+
+    #[test]
+    fn rgbaimage_laplacian_pyramid() {
+        let mut image = RgbaImage::new(8, 6);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 8.0, y as f32 / 6.0, ((x * y) % 3) as f32 / 2.0, 1.0));
+        let pyramid = image.laplacian_pyramid(3);
+        let sizes: Vec<_> = pyramid.iter().map(|p| (p.width(), p.height())).collect();
+        assert_eq!(sizes, vec![(8, 6), (4, 3), (2, 2)]);
+
+        let rebuilt = RgbaImage::reconstruct_from_laplacian_pyramid(&pyramid);
+        assert_eq!((rebuilt.width(), rebuilt.height()), (8, 6));
+        for c in 0..4 {
+            for i in 0..48 {
+                assert!((rebuilt.image[c][i] - image.image[c][i]).abs() < 1e-5);
+            }
+        }
+        assert_eq!(image.laplacian_pyramid(1).len(), 1);
+        assert!(image.laplacian_pyramid(0).is_empty());
+    }
+
+    #[test]
+    fn rgbaimage_channel_correlation_matrix() {
+        let mut image = RgbaImage::new(4, 1);
+        // Green follows red, blue goes against it, and alpha is constant
+        image.for_each_pixel_mut(|x, _, c| {
+            let v = x as f32 / 3.0;
+            *c = Colora::rgb(v, 0.5 * v + 0.25, 1.0 - v, 1.0)
+        });
+        let m = channel_correlation_matrix(&image);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(close(m[0][1], 1.0) && close(m[1][0], 1.0));
+        assert!(close(m[0][2], -1.0) && close(m[1][2], -1.0));
+        assert_eq!(m[0][3], 0.0);
+        assert_eq!(m[3][3], 1.0);
+        for i in 0..4 {
+            assert_eq!(m[i][i], 1.0);
+        }
+    }
+
+    #[test]
+    fn rgbaimage_srgb_round_trip() {
+        let mut image = RgbaImage::new(4, 4);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 3.0, y as f32 / 3.0, 0.5, 0.25 * x as f32));
+        let original = image.clone();
+        image.to_linear();
+        assert_eq!(image.alpha().to_vec(), original.alpha().to_vec());
+        assert!(image.red()[1] < original.red()[1]);
+        image.to_srgb();
+        assert_eq!(image.alpha().to_vec(), original.alpha().to_vec());
+        for c in 0..3 {
+            for i in 0..16 {
+                assert!((image.image[c][i] - original.image[c][i]).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn rgbaimage_non_local_means() {
+        // Left half is dark, right half is bright, with a little checkered noise on top
+        let mut image = RgbaImage::new(8, 8);
+        image.for_each_pixel_mut(|x, y, c| {
+            let base = if x < 4 { 0.2 } else { 0.8 };
+            let noise = if (x + y) % 2 == 0 { 0.05 } else { -0.05 };
+            *c = Colora::rgb(base + noise, base + noise, base + noise, 0.5)
+        });
+        let denoised = image.non_local_means(0.1, 3, 5);
+        let spread = |img: &RgbaImage, x0: usize| {
+            let values: Vec<f32> = (0..8).flat_map(|y| (x0..x0+4).map(move |x| y * 8 + x)).map(|i| img.red()[i]).collect();
+            values.iter().cloned().fold(0.0, f32::max) - values.iter().cloned().fold(1.0, f32::min)
+        };
+        // The noise shrinks on both sides...
+        assert!(spread(&denoised, 0) < spread(&image, 0));
+        assert!(spread(&denoised, 4) < spread(&image, 4));
+        // ...but the edge between them stays sharp
+        assert!(denoised.red()[3] < 0.3 && denoised.red()[4] > 0.7);
+        assert_eq!(denoised.alpha().to_vec(), image.alpha().to_vec());
+    }
+
+    #[test]
+    fn rgbaimage_flip_horizontal() {
+        let mut image = RgbaImage::new(3, 2);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 4.0, y as f32 / 4.0, 0.0, 1.0 - x as f32 / 4.0));
+        assert!(image.flip_horizontal().is_ok());
+        assert_eq!(image.red().to_vec(), vec![0.5, 0.25, 0.0, 0.5, 0.25, 0.0]);
+        assert_eq!(image.green().to_vec(), vec![0.0, 0.0, 0.0, 0.25, 0.25, 0.25]);
+        assert_eq!(image.alpha().to_vec(), vec![0.5, 0.75, 1.0, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn rgbaimage_posterize() {
+        let mut image = RgbaImage::new(4, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(x as f32 / 3.0, 0.3, 0.8, 0.4));
+        assert!(image.posterize(2).is_ok());
+        assert_eq!(image.red().to_vec(), vec![0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(image.green().to_vec(), vec![0.0; 4]);
+        assert_eq!(image.blue().to_vec(), vec![1.0; 4]);
+        assert_eq!(image.alpha().to_vec(), vec![0.4; 4]);
+        assert!(image.posterize(0).is_err());
+    }
+
+    #[test]
+    fn rgbaimage_adjust_brightness() {
+        let mut image = RgbaImage::new(2, 2);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        image.for_each_pixel_mut(|_, _, c| *c = Colora::rgb(0.5, 0.5, 0.5, 0.5));
+        image.adjust_brightness(0.25);
+        assert_eq!(image.red().to_vec(), vec![0.75; 4]);
+        assert_eq!(image.blue().to_vec(), vec![0.75; 4]);
+        assert_eq!(image.alpha().to_vec(), vec![0.5; 4]);
+        image.adjust_brightness(0.5);
+        assert_eq!(image.green().to_vec(), vec![1.0; 4]);
+        // Hidden channels are adjusted too, like every other tone adjustment
+        image.set_channel_visible(&RgbaChannel::Red, false);
+        image.adjust_brightness(-0.5);
+        assert_eq!(image.red().to_vec(), vec![0.5; 4]);
+        assert_eq!(image.green().to_vec(), vec![0.5; 4]);
+        assert!(!image.is_red_visible());
+    }
+
+    #[test]
+    fn rgbaimage_tone_adjustments_ignore_visibility() {
+        let mut hidden = RgbaImage::new(2, 1);
+        hidden.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(0.25 + 0.5 * x as f32, 0.5, 0.1, 0.3));
+        let mut visible = hidden.clone();
+        for c in visible.channel_names() {
+            visible.set_channel_visible(&c, true);
+        }
+        for image in [&mut hidden, &mut visible].iter_mut() {
+            image.adjust_brightness(0.1);
+            image.adjust_contrast(1.5);
+            image.apply_gamma(2.0);
+            image.invert();
+        }
+        assert!(hidden.deep_equal(&visible));
+        assert!(hidden.red()[0] != 0.25);
+        assert_eq!(hidden.alpha().to_vec(), vec![0.3; 2]);
+    }
+
+    #[test]
+    fn rgbaimage_adjust_contrast() {
+        // Visibility is never set, and doesn't matter
+        let mut image = RgbaImage::new(3, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(0.25 * (x + 1) as f32, 0.5, 0.1, 0.3));
+        let original = image.clone();
+        image.adjust_contrast(1.0);
+        assert!(image.close_to(&original, 1e-6));
+        image.adjust_contrast(2.0);
+        assert_eq!(image.red().to_vec(), vec![0.0, 0.5, 1.0]);
+        assert_eq!(image.green().to_vec(), vec![0.5; 3]);
+        assert!(image.blue().iter().all(|v| *v == 0.0));
+        assert_eq!(image.alpha().to_vec(), vec![0.3; 3]);
+    }
+
+    #[test]
+    fn rgbaimage_exposure_adjust() {
+        let mut image = RgbaImage::new(2, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(0.2 + 0.4 * x as f32, 0.25, 0.0, 0.5));
+        let brighter = image.exposure_adjust(1.0);
+        assert_eq!(brighter.red().to_vec(), vec![0.4, 1.0]);
+        assert_eq!(brighter.green().to_vec(), vec![0.5; 2]);
+        assert_eq!(brighter.alpha().to_vec(), vec![0.5; 2]);
+        let darker = image.exposure_adjust(-2.0);
+        assert_eq!(darker.green().to_vec(), vec![0.0625; 2]);
+        assert!(image.exposure_adjust(0.0).close_to(&image, 1e-6));
+        // The original is untouched
+        assert_eq!(image.green().to_vec(), vec![0.25; 2]);
+    }
+
+    #[test]
+    fn rgbaimage_apply_gamma() {
+        // Visibility is never set, and doesn't matter
+        let mut image = RgbaImage::new(3, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(0.5 * x as f32, 0.5, 0.5, 0.5));
+        let original = image.clone();
+        image.apply_gamma(1.0);
+        assert!(image.close_to(&original, 1e-6));
+        image.apply_gamma(2.2);
+        assert!((image.green()[0] - 0.5f32.powf(1.0 / 2.2)).abs() < 1e-6);
+        assert!(image.green()[0] > 0.5);
+        // Black and white stay put
+        assert_eq!((image.red()[0], image.red()[2]), (0.0, 1.0));
+        assert_eq!(image.alpha().to_vec(), vec![0.5; 3]);
+    }
+
+    #[test]
+    fn rgbaimage_shadows_highlights() {
+        let mut image = RgbaImage::new(4, 1);
+        let values = [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0];
+        image.for_each_pixel_mut(|x, _, c| *c = Colora::rgb(values[x], values[x], values[x], 0.5));
+        assert!(image.shadows_highlights(0.0, 0.0).close_to(&image, 1e-6));
+
+        let lifted = image.shadows_highlights(0.2, 0.0);
+        assert!((lifted.red()[1] - (1.0 / 3.0 + 0.2)).abs() < 1e-5);
+        assert!(lifted.red()[2] > 2.0 / 3.0);
+        let rolled = image.shadows_highlights(0.0, 0.2);
+        assert!((rolled.green()[2] - (2.0 / 3.0 - 0.2)).abs() < 1e-5);
+        assert!(rolled.green()[1] < 1.0 / 3.0);
+
+        let both = image.shadows_highlights(0.5, 0.5);
+        assert_eq!((both.blue()[0], both.blue()[3]), (0.0, 1.0));
+        assert_eq!(both.alpha().to_vec(), vec![0.5; 4]);
+    }
+
+    #[test]
+    fn rgbaimage_to_grayscale() {
+        let mut image = RgbaImage::new(2, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = if x == 0 { Colora::rgb(0.0, 1.0, 0.0, 0.5) } else { Colora::rgb(1.0, 1.0, 1.0, 1.0) });
+        image.to_grayscale();
+        for channel in [image.red(), image.green(), image.blue()].iter() {
+            assert!((channel[0] - 0.7152).abs() < 1e-6);
+            assert!((channel[1] - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(image.alpha().to_vec(), vec![0.5, 1.0]);
+    }
+
+    #[test]
+    fn rgbaimage_dehaze() {
+        // A scene where every pixel has a black channel, seen through haze of 0.8 gray at half
+        // transmission, plus one pixel of pure haze (sky)
+        let mut image = RgbaImage::new(4, 4);
+        image.for_each_pixel_mut(|x, y, c| {
+            let haze = |v: f32| v * 0.5 + 0.8 * 0.5;
+            *c = if (x, y) == (3, 3) {
+                Colora::rgb(0.8, 0.8, 0.8, 0.5)
+            } else {
+                Colora::rgb(haze(x as f32 / 3.0), haze(0.0), haze(y as f32 / 3.0), 0.5)
+            };
+        });
+        assert!(image.dehaze(0.0).close_to(&image, 1e-6));
+
+        let clear = image.dehaze(1.0);
+        for x in 0..3 {
+            assert!((clear.red()[x] - x as f32 / 3.0).abs() < 0.05);
+            assert!(clear.green()[x] < 0.05);
+        }
+        assert!((clear.blue()[8] - 2.0 / 3.0).abs() < 0.05);
+        assert_eq!(clear.alpha().to_vec(), vec![0.5; 16]);
+
+        // Half strength lands in between
+        let half = image.dehaze(0.5);
+        assert!(half.green()[0] < image.green()[0] && half.green()[0] > clear.green()[0]);
+    }
+
+    #[test]
+    fn rgbaimage_sepia() {
+        let mut image = RgbaImage::new(2, 1);
+        image.for_each_pixel_mut(|x, _, c| *c = if x == 0 { Colora::rgb(1.0, 1.0, 1.0, 0.5) } else { Colora::rgb(0.5, 0.25, 0.0, 1.0) });
+        image.sepia();
+        // White turns a warm off-white
+        assert_eq!((image.red()[0], image.green()[0]), (1.0, 1.0));
+        assert!((image.blue()[0] - 0.937).abs() < 1e-5);
+        assert!((image.red()[1] - 0.38875).abs() < 1e-5);
+        assert!((image.green()[1] - 0.346).abs() < 1e-5);
+        assert!((image.blue()[1] - 0.2695).abs() < 1e-5);
+        assert_eq!(image.alpha().to_vec(), vec![0.5, 1.0]);
+    }
+
+    #[test]
+    fn rgbaimage_fill() {
+        let mut image = RgbaImage::new(3, 2);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        let color = Colora::rgb(0.25, 0.5, 0.75, 0.5);
+        image.fill(color);
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(image.pixel(x, y).ok(), Some(color));
+            }
+        }
+    }
+
+    #[test]
+    fn rgbaimage_invert() {
+        // Visibility is never set, and doesn't matter
+        let mut image = RgbaImage::new(2, 2);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32, 0.25 * y as f32, 0.75, 0.5));
+        image.invert();
+        assert_eq!(image.red().to_vec(), vec![1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(image.green().to_vec(), vec![1.0, 1.0, 0.75, 0.75]);
+        assert_eq!(image.blue().to_vec(), vec![0.25; 4]);
+        assert_eq!(image.alpha().to_vec(), vec![0.5; 4]);
+        // Inverting twice gets the original back
+        image.invert();
+        assert_eq!(image.green().to_vec(), vec![0.0, 0.0, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn rgbaimage_flip_vertical() {
+        let mut image = RgbaImage::new(2, 3);
+        image.for_each_pixel_mut(|x, y, c| *c = Colora::rgb(x as f32 / 4.0, y as f32 / 4.0, 0.0, 1.0));
+        assert!(image.flip_vertical().is_ok());
+        assert_eq!(image.red().to_vec(), vec![0.0, 0.25, 0.0, 0.25, 0.0, 0.25]);
+        assert_eq!(image.green().to_vec(), vec![0.5, 0.5, 0.25, 0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn rgbaimage_validate() {
+        let mut image = RgbaImage::new(3, 2);
+        assert!(image.validate().is_ok());
+        image.green_mut().write(4, 1.5);
+        let err = image.validate().unwrap_err();
+        assert_eq!(err.4, 4);
+        assert_eq!(format!("{}", err), "got 1.5 at index 4, expected value in [0, 1]");
+    }
+
+    #[test]
+    fn rotate90() {
+        let mut image = RgbaImage::new(2, 3);
+        image.set_pixel(0, 0, Colora::rgb(1.0, 0.0, 0.0, 1.0)).unwrap();
+        image.set_pixel(1, 2, Colora::rgb(0.0, 0.0, 1.0, 1.0)).unwrap();
+        image.set_channel_visible(&RgbaChannel::Red, true);
+        image.set_channel_visible(&RgbaChannel::Blue, true);
+
+        let rotated = image.rotate90().unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (3, 2));
+        assert!(rotated.is_red_visible() && !rotated.is_green_visible());
+        // The top-left corner ends up top-right, and bottom-right ends up bottom-left
+        assert_eq!(rotated.pixel(2, 0).unwrap(), Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(rotated.pixel(0, 1).unwrap(), Colora::rgb(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(rotated.pixel(0, 0).unwrap(), Colora::rgb(0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn rotate180() {
+        let mut image = RgbaImage::new(3, 2);
+        image.set_pixel(0, 0, Colora::rgb(1.0, 0.5, 0.0, 1.0)).unwrap();
+        image.set_pixel(1, 1, Colora::rgb(0.0, 0.0, 1.0, 0.5)).unwrap();
+        let original = image.clone();
+
+        image.rotate180();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.red()[5], 1.0);
+        assert_eq!(image.blue()[1], 1.0);
+        image.rotate180();
+        assert_eq!(image.data(), original.data());
+    }
+
+    #[test]
+    fn channel_error_conversion() {
+        use image::ChannelError;
+        use format::ImageFormatError;
+
+        fn write_red(image: &mut RgbaImage, i: usize) -> Result<(), ImageFormatError<RgbaChannel>> {
+            image.red_mut().try_write(i, 1.0)?;
+            Ok(())
+        }
+        let mut image = RgbaImage::new(2, 2);
+        assert!(write_red(&mut image, 3).is_ok());
+        match write_red(&mut image, 4) {
+            Err(ImageFormatError::Channel(ChannelError::OutOfBounds { index: 4, len: 4 })) => {},
+            other => panic!("expected a channel error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rotate270() {
+        let mut image = RgbaImage::new(2, 3);
+        image.set_pixel(0, 0, Colora::rgb(1.0, 0.0, 0.0, 1.0)).unwrap();
+        image.set_pixel(1, 1, Colora::rgb(0.0, 1.0, 0.0, 0.5)).unwrap();
+
+        let rotated = image.rotate270().unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (3, 2));
+        // The top-left corner ends up bottom-left
+        assert_eq!(rotated.red()[3], 1.0);
+
+        let back = image.rotate90().unwrap().rotate270().unwrap();
+        assert_eq!((back.width(), back.height()), (2, 3));
+        assert_eq!(back.data(), image.data());
+    }
+
+    #[test]
+    fn rotate_arbitrary() {
+        use std::f32::consts::PI;
+
+        let mut image = RgbaImage::new(3, 3);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        for i in 0..9 {
+            image.red_mut().write(i, i as f32 / 8.0);
+            image.green_mut().write(i, 1.0 - i as f32 / 8.0);
+        }
+        let background = Colora::rgb(0.0, 0.0, 1.0, 0.0);
+
+        let same = image.rotate(0.0, background);
+        assert_eq!(same.data(), image.data());
+
+        let quarter = image.rotate(PI / 2.0, background);
+        let exact = image.rotate90().unwrap();
+        for (a, b) in quarter.flat_data().iter().zip(exact.flat_data().iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+
+        // Turned 45°, the corners come from outside the image, but the center stays put
+        let eighth = image.rotate(PI / 4.0, background);
+        assert_eq!(eighth.pixel(0, 0).unwrap(), background);
+        assert_eq!(eighth.pixel(1, 1).unwrap(), image.pixel(1, 1).unwrap());
+
+        // Hidden channels are turned too, and get the background's values
+        let mut hidden = RgbaImage::new(3, 3);
+        hidden.red_mut().fill(0.5);
+        let eighth = hidden.rotate(PI / 4.0, background);
+        assert_eq!(eighth.data()[0], vec![0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(eighth.data()[4], vec![0.5, 0.0, 0.0, 1.0]);
+        assert!(!eighth.is_red_visible());
+    }
+
+    #[test]
+    fn deep_equal() {
+        use format::{YCbCrImage, ChromaSubsampling};
+
+        // Visibility is never set, and doesn't matter
+        let image = RgbaImage::new(2, 2);
+        let mut other = image.clone();
+        assert!(image.deep_equal(&other));
+
+        other.set_pixel(1, 1, Colora::rgb(0.5, 0.0, 0.0, 1.0)).unwrap();
+        assert!(!image.deep_equal(&other));
+        other.set_pixel(1, 1, Colora::rgb(0.0, 0.0, 0.0, 1.0)).unwrap();
+        assert!(image.deep_equal(&other));
+        other.set_channel_visible(&RgbaChannel::Green, true);
+        assert!(image.deep_equal(&other));
+        assert!(!image.deep_equal(&RgbaImage::new(1, 4)));
+
+        // Other formats can be compared too, but store different values
+        assert!(!image.deep_equal(&YCbCrImage::new(2, 2, ChromaSubsampling::Sampling444)));
+    }
+
+    #[test]
+    fn crop() {
+        let mut image = RgbaImage::new(4, 4);
+        image.set_channel_visible(&RgbaChannel::Green, true);
+        for i in 0..16 {
+            image.red_mut().write(i, i as f32 / 16.0);
+        }
+
+        let center = image.crop(1, 1, 2, 2).unwrap();
+        assert_eq!((center.width(), center.height()), (2, 2));
+        assert_eq!(center.red().to_vec(), vec![5.0 / 16.0, 6.0 / 16.0, 9.0 / 16.0, 10.0 / 16.0]);
+        assert!(center.is_green_visible() && !center.is_red_visible());
+        assert!(image.crop(3, 3, 2, 1).is_err());
+        // Rectangles that would overflow are out of bounds too
+        assert!(image.crop(::std::usize::MAX, 0, 2, 1).is_err());
+        assert!(image.crop(0, 1, 1, ::std::usize::MAX).is_err());
+    }
+
+    #[test]
+    fn close_to() {
+        let mut image = RgbaImage::new(2, 2);
+        image.set_pixel(0, 1, Colora::rgb(0.5, 0.25, 0.75, 1.0)).unwrap();
+        let mut other = image.clone();
+        assert!(image.close_to(&other, 1e-6));
+
+        other.set_pixel(0, 1, Colora::rgb(0.5, 0.25, 0.76, 1.0)).unwrap();
+        assert!(image.close_to(&other, 0.02));
+        assert!(!image.close_to(&other, 0.005));
+        assert!(!image.close_to(&RgbaImage::new(4, 1), 1.0));
+    }
+
+    #[test]
+    fn resize_nearest() {
+        let mut image = RgbaImage::new(2, 2);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        let colors = [
+            Colora::rgb(1.0, 0.0, 0.0, 1.0), Colora::rgb(0.0, 1.0, 0.0, 1.0),
+            Colora::rgb(0.0, 0.0, 1.0, 1.0), Colora::rgb(1.0, 1.0, 1.0, 0.5),
+        ];
+        for (i, c) in colors.iter().enumerate() {
+            image.set_pixel(i % 2, i / 2, *c).unwrap();
+        }
+
+        let big = image.resize_nearest(4, 4);
+        assert_eq!((big.width(), big.height()), (4, 4));
+        // Each source pixel becomes a 2x2 block
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(big.pixel(x, y).unwrap(), colors[(y / 2)*2 + x / 2]);
+            }
+        }
+        assert_eq!(image.resize_nearest(1, 1).pixel(0, 0).unwrap(), colors[0]);
+
+        // Hidden channels are resized too
+        let mut hidden = RgbaImage::new(2, 1);
+        hidden.red_mut().write(1, 0.5);
+        let big = hidden.resize_nearest(4, 1);
+        assert_eq!(big.red().to_vec(), vec![0.0, 0.0, 0.5, 0.5]);
+        assert_eq!(big.alpha().to_vec(), vec![1.0; 4]);
+        assert!(!big.is_red_visible());
+    }
+
+    #[test]
+    fn resize_bilinear() {
+        let mut image = RgbaImage::new(2, 1);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        image.set_pixel(1, 0, Colora::rgb(1.0, 0.0, 0.0, 1.0)).unwrap();
+
+        let wide = image.resize_bilinear(4, 2);
+        assert_eq!((wide.width(), wide.height()), (4, 2));
+        // The ends are clamped to the source, the middle is blended
+        for y in 0..2 {
+            assert_eq!(wide.red().to_vec()[y*4..y*4 + 4].to_vec(), vec![0.0, 0.25, 0.75, 1.0]);
+        }
+        assert_eq!(wide.alpha().to_vec(), vec![1.0; 8]);
+
+        // Hidden channels are resized too
+        let mut hidden = RgbaImage::new(2, 1);
+        hidden.red_mut().write(1, 1.0);
+        hidden.alpha_mut().write(0, 0.0);
+        let wide = hidden.resize_bilinear(4, 1);
+        assert_eq!(wide.red().to_vec(), vec![0.0, 0.25, 0.75, 1.0]);
+        assert_eq!(wide.alpha().to_vec(), vec![0.0, 0.25, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn resize_bicubic() {
+        let mut image = RgbaImage::new(5, 3);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        let flat = Colora::rgb(0.2, 0.4, 0.6, 0.8);
+        for y in 0..3 {
+            for x in 0..5 {
+                image.set_pixel(x, y, flat).unwrap();
+            }
+        }
+
+        for &(w, h) in &[(2, 2), (9, 7)] {
+            let resized = image.resize_bicubic(w, h);
+            assert_eq!((resized.width(), resized.height()), (w, h));
+            assert!(resized.validate().is_ok());
+            for (a, b) in resized.flat_data().iter().zip([0.2, 0.4, 0.6, 0.8].iter().cycle()) {
+                assert!((a - b).abs() < 1e-5);
+            }
+        }
+
+        // Overshoot at a hard edge is clamped
+        let mut edge = RgbaImage::new(4, 1);
+        edge.set_channel_visible(&RgbaChannel::Red, true);
+        edge.red_mut().write(2, 1.0);
+        edge.red_mut().write(3, 1.0);
+        assert!(edge.resize_bicubic(7, 1).validate().is_ok());
+
+        // Hidden channels are resized too
+        let mut hidden = RgbaImage::new(3, 3);
+        hidden.green_mut().fill(0.5);
+        let resized = hidden.resize_bicubic(6, 6);
+        assert!(resized.green().all(|v| (v - 0.5).abs() < 1e-5));
+        assert!(resized.red().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn downsample() {
+        use image::ChannelError;
+        use format::ImageFormatError;
+
+        let mut image = RgbaImage::new(4, 4);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        for i in 0..16 {
+            image.red_mut().write(i, i as f32 / 16.0);
+        }
+
+        let small = image.downsample(2).unwrap();
+        assert_eq!((small.width(), small.height()), (2, 2));
+        // The top-left block holds 0, 1, 4 and 5
+        let expected = [2.5, 4.5, 10.5, 12.5];
+        for (i, e) in expected.iter().enumerate() {
+            assert!((small.red()[i] - e / 16.0).abs() < 1e-6);
+        }
+        assert_eq!(small.alpha().to_vec(), vec![1.0; 4]);
+
+        match image.downsample(3) {
+            Err(ImageFormatError::Channel(ChannelError::NotDivisible { len: 4, divisor: 3 })) => {},
+            other => panic!("expected NotDivisible, got {:?}", other.map(|i| i.width()))
+        }
+        assert!(image.downsample(0).is_err());
+
+        // Hidden channels are averaged too
+        let mut hidden = RgbaImage::new(2, 2);
+        hidden.blue_mut().write(3, 1.0);
+        let small = hidden.downsample(2).unwrap();
+        assert_eq!(small.blue().to_vec(), vec![0.25]);
+        assert_eq!(small.alpha().to_vec(), vec![1.0]);
+    }
+
+    #[test]
+    fn raw_planar() {
+        use super::RawPlanar;
+
+        let mut image = RgbaImage::new(2, 1);
+        image.set_pixel(1, 0, Colora::rgb(1.0, 0.5, 0.0, 0.2)).unwrap();
+        let raw = image.encode_raw_planar();
+        assert_eq!(raw, RawPlanar {
+            r: vec![0, 255], g: vec![0, 128], b: vec![0, 0], a: vec![255, 51],
+            width: 2, height: 1
+        });
+
+        let decoded = RgbaImage::decode_raw_planar(&raw);
+        assert_eq!((decoded.width(), decoded.height()), (2, 1));
+        assert!(decoded.close_to(&image, 1.0 / 255.0));
+        assert_eq!(decoded.encode_raw_planar(), raw);
+    }
+
+    #[test]
+    fn try_decode_raw_planar() {
+        use super::RawPlanar;
+        use format::ImageFormatError;
+        use image::ChannelError;
+
+        let raw = RawPlanar { r: vec![0; 4], g: vec![0; 3], b: vec![0; 4], a: vec![0; 4], width: 2, height: 2 };
+        match RgbaImage::try_decode_raw_planar(&raw) {
+            Err(ImageFormatError::MissingData(RgbaChannel::Green, 1, 1)) => {},
+            other => panic!("expected MissingData, got {:?}", other)
+        }
+        let raw = RawPlanar { g: vec![0; 4], a: vec![0; 5], ..raw };
+        match RgbaImage::try_decode_raw_planar(&raw) {
+            Err(ImageFormatError::Channel(ChannelError::LengthMismatch { expected: 4, got: 5 })) => {},
+            other => panic!("expected LengthMismatch, got {:?}", other)
+        }
+        let raw = RawPlanar { a: vec![255; 4], ..raw };
+        assert!(RgbaImage::try_decode_raw_planar(&raw).unwrap().close_to(&RgbaImage::new(2, 2), 1e-6));
+    }
+
+    #[test]
+    #[should_panic(expected = "RawPlanar Blue plane has 1 values")]
+    fn decode_raw_planar_short_plane() {
+        use super::RawPlanar;
+
+        let raw = RawPlanar { r: vec![0; 2], g: vec![0; 2], b: vec![0; 1], a: vec![0; 2], width: 2, height: 1 };
+        RgbaImage::decode_raw_planar(&raw);
+    }
+
+    #[test]
+    fn paste() {
+        let mut image = RgbaImage::new(4, 4);
+        let mut sprite = RgbaImage::new(2, 2);
+        for i in 0..4 {
+            sprite.red_mut().write(i, 1.0);
+            sprite.alpha_mut().write(i, 0.5);
+        }
+
+        image.paste(&sprite, 2, 2).unwrap();
+        for i in 0..16 {
+            let inside = i % 4 >= 2 && i / 4 >= 2;
+            assert_eq!(image.red()[i], if inside { 1.0 } else { 0.0 });
+            assert_eq!(image.alpha()[i], if inside { 0.5 } else { 1.0 });
+        }
+
+        // Pasting over the edge clips
+        let mut image = RgbaImage::new(4, 4);
+        image.paste(&sprite, 3, 0).unwrap();
+        assert_eq!(image.red().iter().filter(|v| **v == 1.0).count(), 2);
+        assert_eq!((image.red()[3], image.red()[7]), (1.0, 1.0));
+        image.paste(&sprite, 4, 4).unwrap();
+        // Entirely off the right edge, on the last row, changes nothing
+        let before = image.clone();
+        assert!(image.paste(&sprite, 6, 3).is_ok());
+        assert!(image.close_to(&before, 0.0001));
+    }
+
+    #[test]
+    fn to_rgba_u8_planes() {
+        let mut image = RgbaImage::new(1, 2);
+        image.set_pixel(0, 1, Colora::rgb(1.0, 0.0, 0.5, 0.0)).unwrap();
+        let (planes, w, h) = image.to_rgba_u8_planes();
+        assert_eq!((w, h), (1, 2));
+        assert_eq!(planes, [vec![0, 255], vec![0, 0], vec![0, 128], vec![255, 0]]);
+    }
+
+    #[test]
+    fn composite() {
+        let mut image = RgbaImage::new(3, 3);
+        let mut block = RgbaImage::new(2, 2);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+            block.set_channel_visible(&c, true);
+        }
+        for i in 0..9 {
+            image.set_pixel(i % 3, i / 3, Colora::rgb(1.0, 1.0, 1.0, 1.0)).unwrap();
+        }
+        for i in 0..4 {
+            block.set_pixel(i % 2, i / 2, Colora::rgb(1.0, 0.0, 0.0, 0.5)).unwrap();
+        }
+
+        image.composite(&block, 2, 1);
+        // Half-transparent red over white is pink, and only inside the image
+        let pink = Colora::rgb(1.0, 0.5, 0.5, 1.0);
+        assert_eq!(image.pixel(2, 1).unwrap(), pink);
+        assert_eq!(image.pixel(2, 2).unwrap(), pink);
+        assert_eq!(image.pixel(1, 1).unwrap(), Colora::rgb(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(image.pixel(2, 0).unwrap(), Colora::rgb(1.0, 1.0, 1.0, 1.0));
+
+        // Hidden channels are blended too, by the stored alpha
+        let mut image = RgbaImage::new(1, 1);
+        let mut dot = RgbaImage::new(1, 1);
+        image.green_mut().write(0, 1.0);
+        dot.red_mut().write(0, 1.0);
+        dot.alpha_mut().write(0, 0.5);
+        image.composite(&dot, 0, 0);
+        assert_eq!(image.data(), vec![vec![0.5, 0.5, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn fill_rect() {
+        let mut image = RgbaImage::new(4, 4);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        let color = Colora::rgb(0.0, 1.0, 0.0, 1.0);
+        image.fill_rect(1, 1, 2, 2, color).unwrap();
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = x >= 1 && x <= 2 && y >= 1 && y <= 2;
+                let expected = if inside { color } else { Colora::rgb(0.0, 0.0, 0.0, 1.0) };
+                assert_eq!(image.pixel(x, y).unwrap(), expected);
+            }
+        }
+
+        // Clipped to the image
+        image.fill_rect(3, 3, 5, 5, color).unwrap();
+        assert_eq!(image.pixel(3, 3).unwrap(), color);
+        assert_eq!(image.green().iter().filter(|v| **v == 1.0).count(), 5);
+        // Even when the far corner doesn't fit in a usize
+        image.fill_rect(2, 0, ::std::usize::MAX, ::std::usize::MAX, color).unwrap();
+        assert_eq!(image.green().iter().filter(|v| **v == 1.0).count(), 10);
+    }
+
+    #[test]
+    fn draw_line() {
+        let mut image = RgbaImage::new(4, 4);
+        image.draw_line(0, 0, 3, 3, Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        let set: Vec<usize> = (0..16).filter(|&i| image.red()[i] == 1.0).collect();
+        assert_eq!(set, vec![0, 5, 10, 15]);
+
+        // The end past the edge is clipped without changing the slope, so this shallow line
+        // never gets to step down
+        let mut image = RgbaImage::new(4, 2);
+        image.draw_line(0, 0, 9, 1, Colora::rgb(0.0, 0.0, 1.0, 1.0));
+        let set: Vec<usize> = (0..8).filter(|&i| image.blue()[i] == 1.0).collect();
+        assert_eq!(set, vec![0, 1, 2, 3]);
+
+        // Starting outside works too, and lines that miss the image draw nothing
+        let mut image = RgbaImage::new(4, 4);
+        image.draw_line(10, 3, 0, 0, Colora::rgb(0.0, 1.0, 0.0, 1.0));
+        let set: Vec<usize> = (0..16).filter(|&i| image.green()[i] == 1.0).collect();
+        assert_eq!((set[0], set[set.len() - 1]), (0, 7));
+        assert!(set.iter().all(|&i| i < 8));
+        image.draw_line(5, 0, 9, 3, Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        image.draw_line(0, ::std::usize::MAX, ::std::usize::MAX, 6, Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        assert!(image.red().iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn draw_rect() {
+        let mut image = RgbaImage::new(5, 5);
+        image.draw_rect(1, 1, 3, 4, Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        let set: Vec<(usize, usize)> = (0..25).filter(|&i| image.red()[i] == 1.0).map(|i| (i % 5, i / 5)).collect();
+        assert_eq!(set, vec![
+            (1, 1), (2, 1), (3, 1),
+            (1, 2), (3, 2),
+            (1, 3), (3, 3),
+            (1, 4), (2, 4), (3, 4),
+        ]);
+
+        // Only the edges inside the image are drawn, so the top and left are all that show
+        let mut image = RgbaImage::new(3, 3);
+        image.draw_rect(1, 1, 4, 4, Colora::rgb(0.0, 1.0, 0.0, 1.0));
+        let set: Vec<usize> = (0..9).filter(|&i| image.green()[i] == 1.0).collect();
+        assert_eq!(set, vec![4, 5, 7]);
+        // Huge rectangles don't overflow, and still only show their top and left edges
+        let mut image = RgbaImage::new(3, 3);
+        image.draw_rect(1, 2, ::std::usize::MAX, ::std::usize::MAX, Colora::rgb(0.0, 1.0, 0.0, 1.0));
+        let set: Vec<usize> = (0..9).filter(|&i| image.green()[i] == 1.0).collect();
+        assert_eq!(set, vec![7, 8]);
+        image.draw_rect(3, 0, 2, 2, Colora::rgb(1.0, 0.0, 0.0, 1.0));
+        assert!(image.red().iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn flood_fill() {
+        let mut image = RgbaImage::new(6, 6);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        // A white box with a nearly black inside, and another black region outside it
+        image.draw_rect(0, 0, 5, 5, Colora::rgb(1.0, 1.0, 1.0, 1.0));
+        image.set_pixel(2, 2, Colora::rgb(0.05, 0.0, 0.0, 1.0)).unwrap();
+
+        let blue = Colora::rgb(0.0, 0.0, 1.0, 1.0);
+        image.flood_fill(1, 1, blue, 0.1).unwrap();
+        let filled: Vec<usize> = (0..36).filter(|&i| image.blue()[i] == 1.0 && image.red()[i] == 0.0).collect();
+        assert_eq!(filled, vec![7, 8, 9, 13, 14, 15, 19, 20, 21]);
+        // The border and the outside are untouched
+        assert_eq!(image.pixel(0, 0).unwrap(), Colora::rgb(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(image.pixel(5, 5).unwrap(), Colora::rgb(0.0, 0.0, 0.0, 1.0));
+
+        // Fill the outside too, leaving the border (also green) between the regions
+        image.flood_fill(5, 0, Colora::rgb(0.0, 1.0, 0.0, 1.0), 0.0).unwrap();
+        assert_eq!(image.green().iter().filter(|v| **v == 1.0).count(), 16 + 11);
+        assert!(image.flood_fill(6, 0, blue, 0.0).is_err());
+    }
 }
@@ -0,0 +1,413 @@
+use image::Channel;
+use palette::{Colora, Rgba};
+use super::{ImageFormat, ImageFormatError, RgbaImage};
+use super::rgba::InvalidData;
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+/// Represents the channels of a YCbCr image
+pub enum YCbCrChannel {
+    /// Luma (brightness) channel
+    Y,
+    /// Blue-difference chroma channel
+    Cb,
+    /// Red-difference chroma channel
+    Cr
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+/// How much the chroma (Cb and Cr) channels are shrunk compared to luma
+pub enum ChromaSubsampling {
+    /// 4:4:4, chroma at full size
+    Sampling444,
+    /// 4:2:2, chroma at half the width
+    Sampling422,
+    /// 4:2:0, chroma at half the width and half the height
+    Sampling420,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+/// The standard used to turn RGB into YCbCr and back
+pub enum YCbCrMatrix {
+    /// ITU-R BT.601 (used by JPEG and SD video)
+    Bt601,
+    /// ITU-R BT.709 (used by HD video)
+    Bt709,
+}
+
+impl YCbCrMatrix {
+    // The red and blue weights of luma (green gets the rest)
+    fn weights(&self) -> (f32, f32) {
+        match *self {
+            YCbCrMatrix::Bt601 => (0.299, 0.114),
+            YCbCrMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+
+    /// Converts an RGB color to (Y, Cb, Cr), with chroma centered on 0.5
+    pub fn rgb_to_ycbcr(&self, r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+        let (kr, kb) = self.weights();
+        let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+        (y, 0.5 + (b - y) / (2.0 * (1.0 - kb)), 0.5 + (r - y) / (2.0 * (1.0 - kr)))
+    }
+
+    /// Converts (Y, Cb, Cr), with chroma centered on 0.5, to an RGB color clamped to [0, 1]
+    pub fn ycbcr_to_rgb(&self, y: f32, cb: f32, cr: f32) -> (f32, f32, f32) {
+        let (kr, kb) = self.weights();
+        let r = y + 2.0 * (1.0 - kr) * (cr - 0.5);
+        let b = y + 2.0 * (1.0 - kb) * (cb - 0.5);
+        let g = (y - kr * r - kb * b) / (1.0 - kr - kb);
+        let clamp = |v: f32| v.max(0.0).min(1.0);
+        (clamp(r), clamp(g), clamp(b))
+    }
+}
+
+impl ChromaSubsampling {
+    // How many luma pixels share a chroma sample, across and down
+    pub(crate) fn factors(&self) -> (usize, usize) {
+        match *self {
+            ChromaSubsampling::Sampling444 => (1, 1),
+            ChromaSubsampling::Sampling422 => (2, 1),
+            ChromaSubsampling::Sampling420 => (2, 2),
+        }
+    }
+}
+
+/// Stores a YCbCr format image (full range, as used by JPEG), with optional chroma subsampling
+// NOTE: The chroma channels are smaller than w * h when subsampled, so provided ImageFormat
+// methods that move channel data directly (flips, rotate90, crop, paste, downsample) report
+// MissingData, and the resizes and rotate leave the chroma blank
+#[derive(Clone, Debug)]
+pub struct YCbCrImage {
+    y: Channel<f32>,
+    cb: Channel<f32>,
+    cr: Channel<f32>,
+    channels: [bool; 3],
+    subsampling: ChromaSubsampling,
+    matrix: YCbCrMatrix,
+    width: usize,
+    height: usize
+}
+
+impl YCbCrImage {
+    /// Creates a new (black) YCbCrImage, using BT.601
+    pub fn new(w: usize, h: usize, subsampling: ChromaSubsampling) -> YCbCrImage {
+        YCbCrImage::with_matrix(w, h, subsampling, YCbCrMatrix::Bt601)
+    }
+
+    /// Creates a new (black) YCbCrImage, using `matrix` to convert colors
+    pub fn with_matrix(w: usize, h: usize, subsampling: ChromaSubsampling, matrix: YCbCrMatrix) -> YCbCrImage {
+        let (fx, fy) = subsampling.factors();
+        let chroma_len = ((w + fx - 1) / fx) * ((h + fy - 1) / fy);
+        YCbCrImage {
+            y: Channel::new(0.0, w * h),
+            cb: Channel::new(0.5, chroma_len),
+            cr: Channel::new(0.5, chroma_len),
+            channels: [false; 3],
+            subsampling: subsampling,
+            matrix: matrix,
+            width: w,
+            height: h
+        }
+    }
+
+    /// Gets the chroma subsampling mode
+    pub fn subsampling(&self) -> ChromaSubsampling {
+        self.subsampling
+    }
+
+    /// Gets the matrix used to convert colors
+    pub fn matrix(&self) -> YCbCrMatrix {
+        self.matrix
+    }
+
+    /// Converts to an RgbaImage with opaque alpha, upsampling chroma. The RGBA channels are
+    /// visible if all of Y, Cb and Cr are
+    pub fn to_rgba(&self) -> RgbaImage {
+        let (w, h) = (self.width, self.height);
+        let mut planes = [vec![0.0; w * h], vec![0.0; w * h], vec![0.0; w * h]];
+        for i in 0..w * h {
+            let (x, y) = (i % w, i / w);
+            let cb = self.sample_chroma(&self.cb, x, y).unwrap_or(0.5);
+            let cr = self.sample_chroma(&self.cr, x, y).unwrap_or(0.5);
+            let (r, g, b) = self.matrix.ycbcr_to_rgb(self.y.get(i).cloned().unwrap_or(0.0), cb, cr);
+            planes[0][i] = r;
+            planes[1][i] = g;
+            planes[2][i] = b;
+        }
+        let mut image = RgbaImage::new(w, h);
+        let visible = self.channels.iter().all(|v| *v);
+        for (name, plane) in image.channel_names().into_iter().zip(planes.iter()) {
+            image.channel_mut(&name).copy_from_slice(plane).expect("YCbCrImage internal error: plane has the wrong size");
+        }
+        for name in image.channel_names() {
+            image.set_channel_visible(&name, visible);
+        }
+        image
+    }
+
+    /// Gets the width of the Cb and Cr channels
+    pub fn chroma_width(&self) -> usize {
+        let (fx, _) = self.subsampling.factors();
+        (self.width + fx - 1) / fx
+    }
+
+    /// Gets the height of the Cb and Cr channels
+    pub fn chroma_height(&self) -> usize {
+        let (_, fy) = self.subsampling.factors();
+        (self.height + fy - 1) / fy
+    }
+
+    fn to_channel(c: &YCbCrChannel) -> usize {
+        match c {
+            &YCbCrChannel::Y => 0,
+            &YCbCrChannel::Cb => 1,
+            &YCbCrChannel::Cr => 2,
+        }
+    }
+
+    /// Samples the chroma channel `c` at the luma pixel (x, y), blending the four nearest
+    /// chroma samples
+    fn sample_chroma(&self, c: &Channel<f32>, x: usize, y: usize) -> Option<f32> {
+        let (fx, fy) = self.subsampling.factors();
+        let (cw, ch) = (self.chroma_width(), self.chroma_height());
+        if c.len() < cw * ch {
+            return None
+        }
+        // Chroma samples sit in the middle of the pixels that share them
+        let sx = ((x as f32 + 0.5) / fx as f32 - 0.5).max(0.0).min((cw - 1) as f32);
+        let sy = ((y as f32 + 0.5) / fy as f32 - 0.5).max(0.0).min((ch - 1) as f32);
+        let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(cw - 1), (y0 + 1).min(ch - 1));
+        let (tx, ty) = (sx - x0 as f32, sy - y0 as f32);
+        let top = c[y0*cw + x0] * (1.0 - tx) + c[y0*cw + x1] * tx;
+        let bottom = c[y1*cw + x0] * (1.0 - tx) + c[y1*cw + x1] * tx;
+        Some(top * (1.0 - ty) + bottom * ty)
+    }
+}
+
+impl ImageFormat<f32> for YCbCrImage {
+    type ChannelName = YCbCrChannel;
+    type ValidationError = InvalidData<f32>;
+
+    fn channel_count(&self) -> usize { 3 }
+    fn channel_names(&self) -> Vec<YCbCrChannel> {
+        vec![YCbCrChannel::Y, YCbCrChannel::Cb, YCbCrChannel::Cr]
+    }
+    fn set_channel_visible(&mut self, c: &YCbCrChannel, enabled: bool) {
+        self.channels[YCbCrImage::to_channel(c)] = enabled;
+    }
+    fn is_channel_visible(&self, c: &YCbCrChannel) -> bool {
+        self.channels[YCbCrImage::to_channel(c)]
+    }
+    // Cb and Cr are offsets from gray, so tone adjustments only touch luma
+    fn is_color_channel(&self, c: &YCbCrChannel) -> bool {
+        *c == YCbCrChannel::Y
+    }
+    fn channel(&self, c: &YCbCrChannel) -> &Channel<f32> {
+        match c {
+            &YCbCrChannel::Y => &self.y,
+            &YCbCrChannel::Cb => &self.cb,
+            &YCbCrChannel::Cr => &self.cr,
+        }
+    }
+    fn channel_mut(&mut self, c: &YCbCrChannel) -> &mut Channel<f32> {
+        match c {
+            &YCbCrChannel::Y => &mut self.y,
+            &YCbCrChannel::Cb => &mut self.cb,
+            &YCbCrChannel::Cr => &mut self.cr,
+        }
+    }
+
+    fn width(&self) -> usize { self.width }
+    fn height(&self) -> usize { self.height }
+    fn with_dimensions(&self, w: usize, h: usize) -> YCbCrImage {
+        let mut image = YCbCrImage::with_matrix(w, h, self.subsampling, self.matrix);
+        image.channels = self.channels;
+        image
+    }
+
+    fn validate(&self) -> Result<(), Self::ValidationError> {
+        for c in [&self.y, &self.cb, &self.cr].iter() {
+            if let Some(at) = c.position(|x| *x > 1.0 || *x < 0.0) {
+                return Err(InvalidData(c[at], 0.0, 1.0, true, at));
+            }
+        }
+        Ok(())
+    }
+
+    // Hidden channels count as black for luma, and neutral (0.5) for chroma
+    fn pixel(&self, x: usize, y: usize) -> Result<Colora, ImageFormatError<YCbCrChannel>> {
+        if x >= self.width || y >= self.height {
+            return Err(ImageFormatError::OutOfBounds(x, y))
+        }
+        let luma = if self.channels[0] {
+            *self.y.get(y*self.width + x).ok_or(ImageFormatError::MissingData(YCbCrChannel::Y, x, y))?
+        } else {
+            0.0
+        };
+        let cb = if self.channels[1] {
+            self.sample_chroma(&self.cb, x, y).ok_or(ImageFormatError::MissingData(YCbCrChannel::Cb, x, y))?
+        } else {
+            0.5
+        };
+        let cr = if self.channels[2] {
+            self.sample_chroma(&self.cr, x, y).ok_or(ImageFormatError::MissingData(YCbCrChannel::Cr, x, y))?
+        } else {
+            0.5
+        };
+        let (r, g, b) = self.matrix.ycbcr_to_rgb(luma, cb, cr);
+        Ok(Colora::rgb(r, g, b, 1.0))
+    }
+
+    // NOTE: When subsampled, pixels share chroma, so this replaces the chroma of the neighbours too
+    fn set_pixel(&mut self, x: usize, y: usize, c: Colora) -> Result<(), ImageFormatError<YCbCrChannel>> {
+        if x >= self.width || y >= self.height {
+            return Err(ImageFormatError::OutOfBounds(x, y))
+        }
+        let (fx, fy) = self.subsampling.factors();
+        let (loc, chroma_loc) = (y*self.width + x, (y / fy)*self.chroma_width() + x / fx);
+        let (r, g, b, _): (f32, f32, f32, f32) = Into::<Rgba>::into(c).to_pixel();
+        let (luma, cb, cr) = self.matrix.rgb_to_ycbcr(r, g, b);
+        self.y.get_mut(loc).map(|v| *v = luma).ok_or(ImageFormatError::MissingData(YCbCrChannel::Y, x, y))?;
+        self.cb.get_mut(chroma_loc).map(|v| *v = cb).ok_or(ImageFormatError::MissingData(YCbCrChannel::Cb, x, y))?;
+        self.cr.get_mut(chroma_loc).map(|v| *v = cr).ok_or(ImageFormatError::MissingData(YCbCrChannel::Cr, x, y))?;
+        Ok(())
+    }
+
+    // Chroma is upsampled, so every pixel gets its own [Y, Cb, Cr]
+    fn data(&self) -> Vec<Vec<f32>> {
+        (0..self.width * self.height).map(|i| {
+            let (x, y) = (i % self.width, i / self.width);
+            vec![
+                self.y.get(i).cloned().unwrap_or(0.0),
+                self.sample_chroma(&self.cb, x, y).unwrap_or(0.5),
+                self.sample_chroma(&self.cr, x, y).unwrap_or(0.5)
+            ]
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{YCbCrImage, YCbCrChannel, ChromaSubsampling, YCbCrMatrix};
+    use format::RgbaImage;
+    use format::ImageFormat;
+    use palette::{Colora, Rgba};
+
+    fn assert_close(a: Colora, b: Colora) {
+        let (a, b): ((f32, f32, f32, f32), (f32, f32, f32, f32)) = (Into::<Rgba>::into(a).to_pixel(), Into::<Rgba>::into(b).to_pixel());
+        for &(x, y) in [(a.0, b.0), (a.1, b.1), (a.2, b.2), (a.3, b.3)].iter() {
+            assert!((x - y).abs() < 1e-3, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn visible(mut image: YCbCrImage) -> YCbCrImage {
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        image
+    }
+
+    #[test]
+    fn ycbcr_sizes() {
+        let image = YCbCrImage::new(5, 3, ChromaSubsampling::Sampling420);
+        assert_eq!((image.chroma_width(), image.chroma_height()), (3, 2));
+        assert_eq!(image.channel(&YCbCrChannel::Y).len(), 15);
+        assert_eq!(image.channel(&YCbCrChannel::Cb).len(), 6);
+        let image = YCbCrImage::new(5, 3, ChromaSubsampling::Sampling422);
+        assert_eq!(image.channel(&YCbCrChannel::Cr).len(), 9);
+        let image = YCbCrImage::new(5, 3, ChromaSubsampling::Sampling444);
+        assert_eq!(image.channel(&YCbCrChannel::Cr).len(), 15);
+        assert!(image.validate().is_ok());
+    }
+
+    #[test]
+    fn ycbcr_pixels() {
+        let mut image = visible(YCbCrImage::new(2, 2, ChromaSubsampling::Sampling444));
+        let colors = [
+            Colora::rgb(1.0, 0.0, 0.0, 1.0), Colora::rgb(0.0, 1.0, 0.0, 1.0),
+            Colora::rgb(0.2, 0.4, 0.6, 1.0), Colora::rgb(1.0, 1.0, 1.0, 1.0),
+        ];
+        for (i, c) in colors.iter().enumerate() {
+            image.set_pixel(i % 2, i / 2, *c).unwrap();
+        }
+        for (i, c) in colors.iter().enumerate() {
+            assert_close(image.pixel(i % 2, i / 2).unwrap(), *c);
+        }
+        assert!(image.pixel(2, 0).is_err());
+        assert!(image.validate().is_ok());
+
+        // Hidden chroma leaves just the gray luma
+        image.set_channel_visible(&YCbCrChannel::Cb, false);
+        image.set_channel_visible(&YCbCrChannel::Cr, false);
+        assert_close(image.pixel(1, 1).unwrap(), Colora::rgb(1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn ycbcr_subsampled() {
+        let mut image = visible(YCbCrImage::new(4, 4, ChromaSubsampling::Sampling420));
+        // Four pixels share one chroma sample, so a flat color survives
+        let color = Colora::rgb(0.8, 0.3, 0.1, 1.0);
+        for i in 0..16 {
+            image.set_pixel(i % 4, i / 4, color).unwrap();
+        }
+        for i in 0..16 {
+            assert_close(image.pixel(i % 4, i / 4).unwrap(), color);
+        }
+
+        // Chroma is blended between samples: pixels 1 and 2 sit 1/4 and 3/4 of the way
+        // between the two chroma columns
+        {
+            let cb = image.channel_mut(&YCbCrChannel::Cb);
+            for (i, v) in [0.25, 0.75, 0.25, 0.75].iter().enumerate() {
+                cb.write(i, *v);
+            }
+        }
+        let data = image.data();
+        let cb: Vec<f32> = data[..4].iter().map(|p| p[1]).collect();
+        assert_eq!(cb, vec![0.25, 0.375, 0.625, 0.75]);
+    }
+
+    #[test]
+    fn ycbcr_matrices() {
+        for matrix in [YCbCrMatrix::Bt601, YCbCrMatrix::Bt709].iter() {
+            let (y, cb, cr) = matrix.rgb_to_ycbcr(1.0, 1.0, 1.0);
+            assert!((y - 1.0).abs() < 1e-5 && (cb - 0.5).abs() < 1e-5 && (cr - 0.5).abs() < 1e-5);
+            let (r, g, b) = matrix.ycbcr_to_rgb(matrix.rgb_to_ycbcr(0.2, 0.7, 0.4).0,
+                matrix.rgb_to_ycbcr(0.2, 0.7, 0.4).1, matrix.rgb_to_ycbcr(0.2, 0.7, 0.4).2);
+            assert!((r - 0.2).abs() < 1e-5 && (g - 0.7).abs() < 1e-5 && (b - 0.4).abs() < 1e-5);
+        }
+        // The standards weigh green differently
+        assert!((YCbCrMatrix::Bt601.rgb_to_ycbcr(0.0, 1.0, 0.0).0 - 0.587).abs() < 1e-5);
+        assert!((YCbCrMatrix::Bt709.rgb_to_ycbcr(0.0, 1.0, 0.0).0 - 0.7152).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ycbcr_rgba_round_trip() {
+        let mut image = RgbaImage::new(4, 2);
+        for c in image.channel_names() {
+            image.set_channel_visible(&c, true);
+        }
+        for i in 0..8 {
+            image.set_pixel(i % 4, i / 4, Colora::rgb(0.1 * i as f32, 0.5, 1.0 - 0.1 * i as f32, 1.0)).unwrap();
+        }
+
+        for matrix in [YCbCrMatrix::Bt601, YCbCrMatrix::Bt709].iter() {
+            let ycbcr = image.to_ycbcr(ChromaSubsampling::Sampling444, *matrix);
+            assert_eq!(ycbcr.matrix(), *matrix);
+            assert!(ycbcr.is_channel_visible(&YCbCrChannel::Cr));
+            let back = ycbcr.to_rgba();
+            assert!(back.close_to(&image, 1e-4));
+            assert!(back.is_alpha_visible());
+        }
+
+        // Subsampled chroma is averaged, so a flat image still survives
+        let mut flat = RgbaImage::new(3, 3);
+        for i in 0..9 {
+            flat.set_pixel(i % 3, i / 3, Colora::rgb(0.9, 0.2, 0.3, 1.0)).unwrap();
+        }
+        let ycbcr = flat.to_ycbcr(ChromaSubsampling::Sampling420, YCbCrMatrix::Bt709);
+        assert_eq!(ycbcr.channel(&YCbCrChannel::Cb).len(), 4);
+        assert!(ycbcr.to_rgba().close_to(&flat, 1e-4));
+    }
+}
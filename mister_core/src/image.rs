@@ -2,26 +2,276 @@
 // XXX: We don't store format anymore. Just channels of equal size.
 //! The formats of images, and how to access and modify them.
 
-use std::ops::{Index, IndexMut};
-use std::fmt::Debug;
+use std::ops::{Index, IndexMut, Range, Add, Mul};
+use std::fmt::{Display, Debug, Formatter, Error};
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::slice;
+use std::iter;
+use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer};
+#[cfg(feature = "fft")]
+use num_complex::Complex;
+
+/// Indicates that an index was outside of the valid range
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub struct OutOfBoundsError {
+    /// The index that was requested
+    pub index: usize,
+    /// The number of valid indices
+    pub len: usize,
+}
+
+impl Display for OutOfBoundsError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "index {} out of bounds for length {}", self.index, self.len)
+    }
+}
+
+impl StdError for OutOfBoundsError {
+    fn description(&self) -> &str { "Index out of bounds" }
+}
+
+/// Indicates that two things that needed the same length didn't have it
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub struct LengthMismatch {
+    /// The length that was needed
+    pub expected: usize,
+    /// The length that was given
+    pub got: usize,
+}
+
+impl Display for LengthMismatch {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "expected length {}, got {}", self.expected, self.got)
+    }
+}
+
+impl StdError for LengthMismatch {
+    fn description(&self) -> &str { "Length mismatch" }
+}
+
+/// Indicates errors for operations on a channel
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ChannelError {
+    /// The length had to be a multiple of some divisor (such as a sampling factor)
+    NotDivisible {
+        /// The length that was given
+        len: usize,
+        /// The number that had to divide it
+        divisor: usize
+    },
+    /// A step size of zero was given
+    ZeroStep,
+    /// Two channels needed the same length, but didn't have it
+    LengthMismatch {
+        /// The length that was needed
+        expected: usize,
+        /// The length that was given
+        got: usize
+    },
+    /// Fewer than two quantization levels were requested
+    TooFewLevels {
+        /// The number of levels that was requested
+        levels: usize
+    },
+    /// An index was outside of the channel
+    OutOfBounds {
+        /// The index that was requested
+        index: usize,
+        /// The length of the channel
+        len: usize
+    },
+    /// An exponent had to be greater than zero
+    NonPositiveExponent,
+    /// A value had to be non-negative, but wasn't
+    NegativeValue {
+        /// The index of the first negative value
+        index: usize
+    },
+}
+
+impl Display for ChannelError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ChannelError::NotDivisible { len, divisor } =>
+                write!(f, "length {} is not a multiple of {}", len, divisor),
+            ChannelError::ZeroStep => write!(f, "step must be non-zero"),
+            ChannelError::LengthMismatch { expected, got } =>
+                write!(f, "expected length {}, got {}", expected, got),
+            ChannelError::TooFewLevels { levels } =>
+                write!(f, "need at least 2 levels, got {}", levels),
+            ChannelError::OutOfBounds { index, len } =>
+                write!(f, "index {} out of bounds for length {}", index, len),
+            ChannelError::NonPositiveExponent => write!(f, "exponent must be greater than zero"),
+            ChannelError::NegativeValue { index } =>
+                write!(f, "value at index {} is negative", index),
+        }
+    }
+}
+
+impl StdError for ChannelError {
+    fn description(&self) -> &str {
+        match *self {
+            ChannelError::NotDivisible { .. } => "Length not divisible",
+            ChannelError::ZeroStep => "Zero step",
+            ChannelError::LengthMismatch { .. } => "Length mismatch",
+            ChannelError::TooFewLevels { .. } => "Too few levels",
+            ChannelError::OutOfBounds { .. } => "Index out of bounds",
+            ChannelError::NonPositiveExponent => "Non-positive exponent",
+            ChannelError::NegativeValue { .. } => "Negative value",
+        }
+    }
+}
+
+impl From<LengthMismatch> for ChannelError {
+    fn from(e: LengthMismatch) -> ChannelError {
+        ChannelError::LengthMismatch { expected: e.expected, got: e.got }
+    }
+}
+
+impl From<OutOfBoundsError> for ChannelError {
+    fn from(e: OutOfBoundsError) -> ChannelError {
+        ChannelError::OutOfBounds { index: e.index, len: e.len }
+    }
+}
+
+/// Indicates errors for operations spanning a whole image
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// The requested index was outside the image
+    OutOfBounds {
+        /// The index that was requested
+        index: usize,
+        /// The number of valid indices
+        len: usize
+    },
+    /// The number of values given didn't match the number of channels
+    ChannelCountMismatch {
+        /// The number of channels in the image
+        expected: usize,
+        /// The number of values that were given
+        got: usize
+    },
+    /// The length given didn't match the length of the image
+    LengthMismatch {
+        /// The length of the image
+        expected: usize,
+        /// The length that was given
+        got: usize
+    },
+    /// The requested rectangle doesn't fit inside the image
+    RegionOutOfBounds {
+        /// Left edge of the rectangle
+        x: usize,
+        /// Top edge of the rectangle
+        y: usize,
+        /// Width of the rectangle
+        width: usize,
+        /// Height of the rectangle
+        height: usize
+    },
+    /// The length had to be a multiple of some divisor (such as a channel count)
+    NotDivisible {
+        /// The length that was given
+        len: usize,
+        /// The number that had to divide it
+        divisor: usize
+    },
+    /// A channel was listed more than once where each had to appear once
+    DuplicateChannel {
+        /// The repeated channel index
+        index: usize
+    },
+    /// The widths of two images didn't match where their rows had to line up
+    WidthMismatch {
+        /// The width of the image
+        expected: usize,
+        /// The width that was given
+        got: usize
+    },
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ImageError::OutOfBounds { index, len } =>
+                write!(f, "index {} out of bounds for length {}", index, len),
+            ImageError::ChannelCountMismatch { expected, got } =>
+                write!(f, "expected {} channels, got {}", expected, got),
+            ImageError::LengthMismatch { expected, got } =>
+                write!(f, "expected length {}, got {}", expected, got),
+            ImageError::RegionOutOfBounds { x, y, width, height } =>
+                write!(f, "region {}x{} at ({}, {}) is outside the image", width, height, x, y),
+            ImageError::NotDivisible { len, divisor } =>
+                write!(f, "length {} is not a multiple of {}", len, divisor),
+            ImageError::DuplicateChannel { index } =>
+                write!(f, "channel {} was given more than once", index),
+            ImageError::WidthMismatch { expected, got } =>
+                write!(f, "expected width {}, got {}", expected, got),
+        }
+    }
+}
+
+impl StdError for ImageError {
+    fn description(&self) -> &str {
+        match *self {
+            ImageError::OutOfBounds { .. } => "Index out of bounds",
+            ImageError::ChannelCountMismatch { .. } => "Channel count mismatch",
+            ImageError::LengthMismatch { .. } => "Length mismatch",
+            ImageError::RegionOutOfBounds { .. } => "Region out of bounds",
+            ImageError::NotDivisible { .. } => "Length not divisible",
+            ImageError::DuplicateChannel { .. } => "Duplicate channel",
+            ImageError::WidthMismatch { .. } => "Width mismatch",
+        }
+    }
+}
+
+impl From<OutOfBoundsError> for ImageError {
+    fn from(e: OutOfBoundsError) -> ImageError {
+        ImageError::OutOfBounds { index: e.index, len: e.len }
+    }
+}
+
+impl From<LengthMismatch> for ImageError {
+    fn from(e: LengthMismatch) -> ImageError {
+        ImageError::LengthMismatch { expected: e.expected, got: e.got }
+    }
+}
 
 // QUESTION: Do we need a constrait on T?
 /// This represent a set of data values for one color.
-#[derive(Clone, Debug)]
+/// Channels are `Send` and `Sync` whenever `T` is, so they can be shared between threads
+// NOTE: Debug only prints the ends of the data, use full_debug() to see everything
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Channel<T: Clone + Debug> {
     // TODO: Maybe look for a fixed length, but resizeable, array structure
     // NOTE: data must guarantee that replacements will keep data @ data.len
-    data: Vec<T>,
+    // NOTE: data is shared between clones, and copied on the first write to a shared channel.
+    // Every mutation MUST go through data_mut()
+    data: Arc<Vec<T>>,
 
     default: T, // Hold onto this, we need it for resizing.
+
+    // The envelope of indices written since the last take_dirty()
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dirty: Option<Range<usize>>,
+
+    // An optional name, set through Image::set_channel_label
+    #[cfg_attr(feature = "serde", serde(default))]
+    label: Option<String>,
 }
 
 impl<T: Clone + Debug> Channel<T> {
     /// Creates a new Channel
     pub fn new(default: T, x: usize) -> Channel<T> {
         Channel {
-            data: vec![default.clone(); x],
-            default: default
+            data: Arc::new(vec![default.clone(); x]),
+            default: default,
+            dirty: None,
+            label: None
         }
     }
 
@@ -43,8 +293,56 @@ impl<T: Clone + Debug> Channel<T> {
     /// Change value at index `i` to `data`
     pub fn write(&mut self, i: usize, data: T) {
         // TODO: Bounds checking
-        self.data.remove(i);
-        self.data.insert(i, data);
+        self.data_mut()[i] = data;
+        self.mark_dirty(i..i+1);
+    }
+
+    /// Change value at index `i` to `data`, or fail if `i` is out of bounds
+    pub fn try_write(&mut self, i: usize, data: T) -> Result<(), ChannelError> {
+        if i >= self.len() {
+            return Err(ChannelError::OutOfBounds { index: i, len: self.len() })
+        }
+        self.write(i, data);
+        Ok(())
+    }
+
+    /// Copy `values` into the channel, starting at index `start`.
+    /// Nothing is written if they don't all fit
+    pub fn write_slice(&mut self, start: usize, values: &[T]) -> Result<(), ChannelError> {
+        let end = start + values.len();
+        if end > self.len() {
+            return Err(ChannelError::OutOfBounds { index: end - 1, len: self.len() })
+        }
+        self.data_mut()[start..end].clone_from_slice(values);
+        self.mark_dirty(start..end);
+        Ok(())
+    }
+
+    /// Replace every value with the ones in `other`, which must have the same length.
+    /// The default value is kept
+    pub fn copy_from(&mut self, other: &Channel<T>) -> Result<(), ChannelError> {
+        if other.len() != self.len() {
+            return Err(ChannelError::LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        self.data = other.data.clone();
+        let len = self.len();
+        self.mark_dirty(0..len);
+        Ok(())
+    }
+
+    /// Combine this channel with `other` value by value. The defaults are combined the same way
+    pub fn zip_with<U, V, F>(&self, other: &Channel<U>, f: F) -> Result<Channel<V>, ChannelError>
+        where U: Clone + Debug, V: Clone + Debug, F: Fn(&T, &U) -> V {
+        if other.len() != self.len() {
+            return Err(ChannelError::LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        let data = self.data.iter().zip(other.data.iter()).map(|(a, b)| f(a, b)).collect();
+        Ok(Channel::from_vec(f(&self.default, &other.default), data))
+    }
+
+    /// Get the label given to this channel, if any
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|l| l.as_str())
     }
 
     /// Retrieve value at index `i`
@@ -54,7 +352,11 @@ impl<T: Clone + Debug> Channel<T> {
 
     /// Retrieve value at index `i` mutably
     pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
-        self.data.get_mut(i)
+        // NOTE: We can't know if the value is actually changed, so assume it is
+        if i < self.len() {
+            self.mark_dirty(i..i+1);
+        }
+        self.data_mut().get_mut(i)
     }
 
     /// Retrieve value at index `i` as a clone (non-reference)
@@ -63,6 +365,188 @@ impl<T: Clone + Debug> Channel<T> {
         self.get(i).cloned()
     }
 
+    /// Set every value back to the default, keeping the length
+    pub fn reset(&mut self) {
+        let default = self.default.clone();
+        for x in self.data_mut().iter_mut() {
+            *x = default.clone();
+        }
+        let len = self.len();
+        self.mark_dirty(0..len);
+    }
+
+    /// Find the index of the first value for which `f` returns true
+    pub fn position<F: Fn(&T) -> bool>(&self, f: F) -> Option<usize> {
+        self.data.iter().position(f)
+    }
+
+    /// Count the values for which `f` returns true
+    pub fn count_where<F: Fn(&T) -> bool>(&self, f: F) -> usize {
+        self.data.iter().filter(|x| f(x)).count()
+    }
+
+    /// Check if `f` returns true for any value. False for an empty channel
+    pub fn any<F: Fn(&T) -> bool>(&self, f: F) -> bool {
+        self.data.iter().any(f)
+    }
+
+    /// Check if `f` returns true for every value. True for an empty channel
+    pub fn all<F: Fn(&T) -> bool>(&self, f: F) -> bool {
+        self.data.iter().all(f)
+    }
+
+    /// Set every value to `value`
+    pub fn fill(&mut self, value: T) {
+        for x in self.data_mut().iter_mut() {
+            *x = value.clone();
+        }
+        let len = self.len();
+        self.mark_dirty(0..len);
+    }
+
+    /// Call `f` with the index and a mutable reference to every value
+    pub fn apply_indexed<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+        for (i, x) in self.data_mut().iter_mut().enumerate() {
+            f(i, x);
+        }
+        let len = self.len();
+        self.mark_dirty(0..len);
+    }
+
+    /// Grow the dirty range to cover `range`
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return
+        }
+        self.dirty = Some(match self.dirty.take() {
+            Some(d) => d.start.min(range.start)..d.end.max(range.end),
+            None => range
+        });
+    }
+
+    /// Get the range of indices written to since the last call, and start tracking anew.
+    /// The range covers every change, but may also cover values that weren't changed
+    pub fn take_dirty(&mut self) -> Option<Range<usize>> {
+        self.dirty.take()
+    }
+
+    /// Alternate the values of this channel and `other`, producing
+    /// `[self[0], other[0], self[1], other[1], ...]`
+    pub fn interleave(&self, other: &Channel<T>) -> Result<Channel<T>, LengthMismatch> {
+        if other.len() != self.len() {
+            return Err(LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        let mut data = Vec::with_capacity(self.len() * 2);
+        for (a, b) in self.data.iter().zip(other.data.iter()) {
+            data.push(a.clone());
+            data.push(b.clone());
+        }
+        Ok(self._with_data(data))
+    }
+
+    /// Split the values into `stride` channels, where channel `k` gets every
+    /// `stride`-th value starting at `k`. This is the inverse of `interleave`.
+    /// The length must be a multiple of `stride`
+    pub fn deinterleave(&self, stride: usize) -> Result<Vec<Channel<T>>, ChannelError> {
+        if stride == 0 {
+            return Err(ChannelError::ZeroStep)
+        }
+        if self.len() % stride != 0 {
+            return Err(ChannelError::NotDivisible { len: self.len(), divisor: stride })
+        }
+        let mut planes = vec![Vec::with_capacity(self.len() / stride); stride];
+        for (i, x) in self.data.iter().enumerate() {
+            planes[i % stride].push(x.clone());
+        }
+        Ok(planes.into_iter().map(|p| self._with_data(p)).collect())
+    }
+
+    /// Keep every `factor`-th value, starting with the first (nearest-neighbor downsampling).
+    /// The length must be a multiple of `factor`
+    pub fn downsample_by(&self, factor: usize) -> Result<Channel<T>, ChannelError> {
+        if factor == 0 || self.len() % factor != 0 {
+            return Err(ChannelError::NotDivisible { len: self.len(), divisor: factor })
+        }
+        let data = self.data.chunks(factor).map(|c| c[0].clone()).collect();
+        Ok(self._with_data(data))
+    }
+
+    /// Take every `step`-th value, starting at index `offset`
+    pub fn stride_sample(&self, step: usize, offset: usize) -> Result<Channel<T>, ChannelError> {
+        if step == 0 {
+            return Err(ChannelError::ZeroStep)
+        }
+        let data = if offset < self.len() {
+            self.data[offset..].chunks(step).map(|c| c[0].clone()).collect()
+        } else {
+            vec![]
+        };
+        Ok(self._with_data(data))
+    }
+
+    /// Follow every value with `factor - 1` copies of `fill`, making the channel `factor`
+    /// times longer (zero-insertion upsampling, when `fill` is zero)
+    pub fn upsample_by(&self, factor: usize, fill: T) -> Channel<T> {
+        let mut data = Vec::with_capacity(self.len() * factor);
+        if factor > 0 {
+            for x in self.data.iter() {
+                data.push(x.clone());
+                for _ in 1..factor {
+                    data.push(fill.clone());
+                }
+            }
+        }
+        self._with_data(data)
+    }
+
+    /// Create a channel from existing data
+    pub(crate) fn from_vec(default: T, data: Vec<T>) -> Channel<T> {
+        Channel {
+            data: Arc::new(data),
+            default: default,
+            dirty: None,
+            label: None
+        }
+    }
+
+    /// Create a channel with the same default as this one, holding `data`
+    fn _with_data(&self, data: Vec<T>) -> Channel<T> {
+        Channel::from_vec(self.default.clone(), data)
+    }
+
+    // Like _with_data, but keeps the label too, for when the values are just moved around
+    fn _with_data_and_label(&self, data: Vec<T>) -> Channel<T> {
+        let mut chan = self._with_data(data);
+        chan.label = self.label.clone();
+        chan
+    }
+
+    /// Get the data for writing, copying it first if another channel shares it
+    fn data_mut(&mut self) -> &mut Vec<T> {
+        Arc::make_mut(&mut self.data)
+    }
+
+    /// Check if the data is currently shared with a clone of this channel
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.data) > 1
+    }
+
+    /// Borrow the values of this channel, without copying them
+    pub fn as_raw(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Copy the values of this channel into a new Vec
+    pub fn to_vec(&self) -> Vec<T> {
+        self.data.as_ref().clone()
+    }
+
+    /// Consume this channel, returning its values without copying them
+    /// (unless they are shared with a clone)
+    pub fn into_vec(self) -> Vec<T> {
+        Arc::try_unwrap(self.data).unwrap_or_else(|shared| shared.as_ref().clone())
+    }
+
     /// Resize channel to `new_len` and returns it
     // NOTE Requires ownership to prevent resizing borrows
     pub fn resize(mut self, new_len: usize) -> Channel<T> {
@@ -74,12 +558,28 @@ impl<T: Clone + Debug> Channel<T> {
     /// Resize channel to `new_len` and returns it
     // NOTE Only requires a mutable *borrow*
     fn _resize(&mut self, new_len: usize) {
-        self.data.truncate(new_len);
-        if self.len() < new_len {
-            let data_len = new_len - self.data.len();
-            let default = self.default.clone();
-            self.data.extend_from_slice(&vec![default; data_len])
+        let old_len = self.len();
+        let default = self.default.clone();
+        {
+            let data = self.data_mut();
+            data.truncate(new_len);
+            if data.len() < new_len {
+                let data_len = new_len - data.len();
+                data.extend_from_slice(&vec![default; data_len])
+            }
         }
+        // Forget about changes that were cut off, and count new values as changed
+        self.dirty = self.dirty.take().map(|d| d.start.min(new_len)..d.end.min(new_len))
+            .and_then(|d| if d.start < d.end { Some(d) } else { None });
+        self.mark_dirty(old_len..new_len);
+    }
+
+    // Swap in new values (possibly of a new length), counting them all as changed
+    fn replace_data(&mut self, data: Vec<T>) {
+        self.data = Arc::new(data);
+        self.dirty = None;
+        let len = self.len();
+        self.mark_dirty(0..len);
     }
 
     /// Create an iterator over the values of this channel
@@ -89,168 +589,1648 @@ impl<T: Clone + Debug> Channel<T> {
             at: 0
         }
     }
+
+    /// Create an iterator over non-overlapping chunks of exactly `size` values.
+    /// Values at the end that don't fill a whole chunk are left out, and can be
+    /// retrieved with `ChannelChunksExact::remainder`
+    // NOTE PANICS if size is 0
+    pub fn chunks_exact(&self, size: usize) -> ChannelChunksExact<T> {
+        assert!(size != 0, "chunk size must be non-zero");
+        let split = self.data.len() - self.data.len() % size;
+        let (data, rem) = self.data.split_at(split);
+        ChannelChunksExact {
+            data: data,
+            rem: rem,
+            size: size
+        }
+    }
 }
 
-// NOTE that Index implementations PANIC at failure
-impl<T: Clone + Debug> Index<usize> for Channel<T> {
-    type Output = T;
-    fn index(&self, i: usize) -> &T {
-        self.get(i).unwrap()
+/// Describes values that can be scaled to and from the normalized range [0, 1]
+pub trait ChannelConvert: Clone + Debug {
+    /// Creates a value from `v` in [0, 1]. Values outside that range are clamped
+    fn from_normalized(v: f32) -> Self;
+    /// Scales this value into [0, 1]
+    fn to_normalized(&self) -> f32;
+}
+
+impl ChannelConvert for f32 {
+    fn from_normalized(v: f32) -> f32 { v }
+    fn to_normalized(&self) -> f32 { *self }
+}
+
+impl ChannelConvert for u8 {
+    fn from_normalized(v: f32) -> u8 {
+        // NOTE max/min drop NaN, so NaN becomes 0
+        (v * 255.0).round().max(0.0).min(255.0) as u8
     }
+    fn to_normalized(&self) -> f32 { *self as f32 / 255.0 }
 }
 
-// NOTE that Index implementations PANIC at failure
-impl<T: Clone + Debug> IndexMut<usize> for Channel<T> {
-    fn index_mut(&mut self, i: usize) -> &mut T {
-        self.get_mut(i).unwrap()
+impl ChannelConvert for u16 {
+    fn from_normalized(v: f32) -> u16 {
+        (v * 65535.0).round().max(0.0).min(65535.0) as u16
     }
+    fn to_normalized(&self) -> f32 { *self as f32 / 65535.0 }
 }
 
-/// Iterates over the data of a channel
-pub struct ChannelIterator<'a, T: Clone + Debug + 'a> {
-    chan: &'a Channel<T>,
-    at: usize
+impl<T: ChannelConvert> Channel<T> {
+    /// Converts every value (and the default) to another scaled type
+    pub fn convert<U: ChannelConvert>(&self) -> Channel<U> {
+        Channel::from_vec(U::from_normalized(self.default.to_normalized()),
+            self.data.iter().map(|x| U::from_normalized(x.to_normalized())).collect())
+    }
 }
 
-impl<'a, T: Clone + Debug + 'a> Iterator for ChannelIterator<'a, T> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<&'a T> {
-        self.at += 1;
-        if self.at - 1 >= self.chan.len() {
-            None
-        } else {
-            self.chan.get(self.at-1)
+/// Tapers applied to a channel before taking its FFT, to reduce spectral leakage
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub enum WindowFunction {
+    /// Raised cosine, reaching 0 at both ends
+    Hann,
+    /// Raised cosine that stops just above 0 at both ends
+    Hamming,
+    /// Three-term cosine, with lower side lobes than Hann
+    Blackman,
+    /// Leaves every value as it is
+    Rectangular,
+}
+
+impl WindowFunction {
+    /// The weight of sample `n` out of `len`. The window is symmetric, peaking in the middle
+    pub fn weight(&self, n: usize, len: usize) -> f32 {
+        if len < 2 {
+            return 1.0
+        }
+        let phase = 2.0 * ::std::f32::consts::PI * n as f32 / (len - 1) as f32;
+        match *self {
+            WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+            WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+            WindowFunction::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+            WindowFunction::Rectangular => 1.0,
         }
     }
+}
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.chan.len(), Some(self.chan.len()))
+impl Channel<f32> {
+    /// Converts to 8-bit values, mapping [0, 1] to [0, 255]
+    pub fn to_u8(&self) -> Channel<u8> {
+        self.convert()
     }
-}
-impl<'a, T: Clone + Debug + 'a> ExactSizeIterator for ChannelIterator<'a, T> {}
 
+    /// Converts to 16-bit values, mapping [0, 1] to [0, 65535]
+    pub fn to_u16(&self) -> Channel<u16> {
+        self.convert()
+    }
 
-// TODO A mutable iterator
+    /// Raise every value to the power of `exponent`
+    pub fn gamma(&mut self, exponent: f32) {
+        self.apply_indexed(|_, v| *v = v.powf(exponent));
+    }
 
-/// A collection of channels to be interpreted in a certain way.
-// NOTE: We DON'T assign a type here. That's MISTER's job...
-#[derive(Clone, Debug)]
-pub struct Image<T: Clone + Debug> {
-    /// NOTE: At this point, we aren't going to even assign a color model, just a configuation of channels
-    channels: Vec<Channel<T>>,
-    /// The size that all channels *must* be.
-    len: usize,
-}
+    /// Decode sRGB values into linear light, using the sRGB transfer function
+    pub fn to_linear(&mut self) {
+        self.apply_indexed(|_, v| {
+            *v = if *v <= 0.04045 { *v / 12.92 } else { ((*v + 0.055) / 1.055).powf(2.4) }
+        });
+    }
 
-impl<T: Clone + Debug> Image<T> {
-    /// Creates a new Image
-    pub fn new(len: usize) -> Image<T> {
-        // NOTE: We start with NO CHANNELS, so something must be done...
-        Image {
-            channels: vec![],
-            len: len
+    /// Encode linear light values as sRGB, using the sRGB transfer function
+    pub fn to_srgb(&mut self) {
+        self.apply_indexed(|_, v| {
+            *v = if *v <= 0.0031308 { *v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+        });
+    }
+
+    /// Copy this channel, with every value raised to the power `gamma`. `gamma` must be positive
+    /// and the values non-negative
+    pub fn power_law_transform(&self, gamma: f32) -> Result<Channel<f32>, ChannelError> {
+        if gamma <= 0.0 || gamma.is_nan() {
+            return Err(ChannelError::NonPositiveExponent)
+        }
+        if let Some(index) = self.position(|v| *v < 0.0) {
+            return Err(ChannelError::NegativeValue { index: index })
         }
+        Ok(self._with_data_and_label(self.data.iter().map(|v| v.powf(gamma)).collect()))
     }
 
-    /// Creates a channel
-    // TODO Add specifics (same with Channel::new)
-    pub fn create_channel(&mut self, default: T) {
-        self.channels.push(Channel::new(default, self.len))
+    /// Copy this channel, with every value multiplied by its weight in the window `kind`
+    pub fn apply_window_function(&self, kind: WindowFunction) -> Channel<f32> {
+        let len = self.len();
+        self._with_data_and_label(self.data.iter().enumerate().map(|(n, v)| v * kind.weight(n, len)).collect())
     }
 
-    // TODO: Bounds-checking
-    /// Access channel at index `i`
-    pub fn channel(&self, i: usize) -> Option<&Channel<T>> {
-        self.channels.get(i)
+    /// Snap every value to the nearest of `levels` evenly spaced values in [0, 1].
+    /// Values outside [0, 1] are clamped first
+    pub fn quantize(&mut self, levels: usize) -> Result<(), ChannelError> {
+        if levels < 2 {
+            return Err(ChannelError::TooFewLevels { levels: levels })
+        }
+        let steps = (levels - 1) as f32;
+        self.apply_indexed(|_, v| *v = (v.max(0.0).min(1.0) * steps).round() / steps);
+        Ok(())
     }
 
-    /// Access channel at index `i` mutably
-    pub fn channel_mut(&mut self, i: usize) -> Option<&mut Channel<T>> {
-        self.channels.get_mut(i)
+    /// Copy this channel, with every value snapped to the nearest of `levels` evenly spaced values
+    pub fn quantized(&self, levels: usize) -> Result<Channel<f32>, ChannelError> {
+        let mut chan = self.clone();
+        chan.quantize(levels)?;
+        Ok(chan)
     }
 
-    /// Get the number of channels
-    pub fn count(&self) -> usize {
-        self.channels.len()
+    /// Population covariance with `other`. Empty channels give 0
+    pub fn covariance(&self, other: &Channel<f32>) -> Result<f32, LengthMismatch> {
+        if other.len() != self.len() {
+            return Err(LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        if self.len() == 0 {
+            return Ok(0.0)
+        }
+        let n = self.len() as f32;
+        let (mean_a, mean_b) = (self.data.iter().sum::<f32>() / n, other.data.iter().sum::<f32>() / n);
+        Ok(self.data.iter().zip(other.data.iter())
+            .map(|(a, b)| (a - mean_a) * (b - mean_b))
+            .sum::<f32>() / n)
     }
 
-    /// Get the length of image, which is the length of each channel
-    pub fn len(&self) -> usize {
-        self.len
+    /// Peak signal-to-noise ratio against `other`, in decibels, taking 1 as the peak value.
+    /// Identical (or empty) channels give infinity
+    pub fn psnr(&self, other: &Channel<f32>) -> Result<f32, LengthMismatch> {
+        if other.len() != self.len() {
+            return Err(LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        if self.len() == 0 {
+            return Ok(::std::f32::INFINITY)
+        }
+        let mse = self.data.iter().zip(other.data.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>() / self.len() as f32;
+        Ok(10.0 * (1.0 / mse).log10())
     }
 
-    /// Resize image to length `new_len`
-    pub fn resize(&mut self, new_len: usize) {
-        self.len = new_len;
-        for c in self.channels.iter_mut() {
-            c._resize(new_len);
+    /// Creates a channel from 8-bit values, mapping [0, 255] to [0, 1]
+    pub fn from_u8_scaled(data: &[u8]) -> Channel<f32> {
+        Channel::from_vec(0.0, data.iter().map(|x| x.to_normalized()).collect())
+    }
+
+    /// Creates a channel from 16-bit values, mapping [0, 65535] to [0, 1]
+    pub fn from_u16_scaled(data: &[u16]) -> Channel<f32> {
+        Channel::from_vec(0.0, data.iter().map(|x| x.to_normalized()).collect())
+    }
+}
+
+mod sealed {
+    // Keeps ChannelBytes from being implemented outside this crate
+    pub trait Sealed {}
+}
+
+/// Describes plain values that can be stored as little endian bytes
+pub trait ChannelBytes: Clone + Debug + sealed::Sealed {
+    /// The number of bytes a value takes up
+    const SIZE: usize;
+    /// Writes the value into the first `SIZE` bytes of `buf`
+    fn write_le(&self, buf: &mut [u8]);
+    /// Reads a value from the first `SIZE` bytes of `buf`
+    fn read_le(buf: &[u8]) -> Self;
+}
+
+macro_rules! channel_bytes {
+    ($t:ty, $size:expr, $read:ident, $write:ident) => {
+        impl sealed::Sealed for $t {}
+        impl ChannelBytes for $t {
+            const SIZE: usize = $size;
+            fn write_le(&self, buf: &mut [u8]) { LittleEndian::$write(buf, *self) }
+            fn read_le(buf: &[u8]) -> $t { LittleEndian::$read(buf) }
         }
     }
 }
 
-// NOTE Index impl PANIC at failure
-impl<T: Clone + Debug> Index<usize> for Image<T> {
-    type Output = Channel<T>;
-    fn index(&self, i: usize) -> &Channel<T> {
-        self.channel(i).unwrap()
+impl sealed::Sealed for u8 {}
+impl ChannelBytes for u8 {
+    const SIZE: usize = 1;
+    fn write_le(&self, buf: &mut [u8]) { buf[0] = *self }
+    fn read_le(buf: &[u8]) -> u8 { buf[0] }
+}
+channel_bytes!(u16, 2, read_u16, write_u16);
+channel_bytes!(f32, 4, read_f32, write_f32);
+channel_bytes!(f64, 8, read_f64, write_f64);
+
+impl<T: ChannelBytes> Channel<T> {
+    /// Stores the values as bytes, in little endian order
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.len() * T::SIZE];
+        for (x, buf) in self.data.iter().zip(bytes.chunks_mut(T::SIZE)) {
+            x.write_le(buf);
+        }
+        bytes
+    }
+
+    /// Reads values stored by `to_bytes`. The number of bytes must be a multiple of the value size
+    pub fn from_bytes(bytes: &[u8], default: T) -> Result<Channel<T>, ChannelError> {
+        if bytes.len() % T::SIZE != 0 {
+            return Err(ChannelError::NotDivisible { len: bytes.len(), divisor: T::SIZE })
+        }
+        Ok(Channel::from_vec(default, bytes.chunks(T::SIZE).map(T::read_le).collect()))
     }
 }
 
-impl<T: Clone + Debug> IndexMut<usize> for Image<T> {
-    fn index_mut(&mut self, i: usize) -> &mut Channel<T> {
-        self.channel_mut(i).unwrap()
+#[cfg(feature = "fft")]
+impl Channel<f32> {
+    /// Wraps every value as a complex number with no imaginary part, ready for FFT filtering
+    pub fn to_complex(&self) -> Channel<Complex<f32>> {
+        Channel::from_vec(Complex::new(self.default, 0.0), self.data.iter().map(|v| Complex::new(*v, 0.0)).collect())
     }
 }
 
+macro_rules! channel_float {
+    ($t:ty) => {
+        impl Channel<$t> {
+            /// Blend towards `other` by `t`, giving `self * (1 - t) + other * t` for each value
+            pub fn lerp(&self, other: &Channel<$t>, t: $t) -> Result<Channel<$t>, ChannelError> {
+                if other.len() != self.len() {
+                    return Err(ChannelError::LengthMismatch { expected: self.len(), got: other.len() })
+                }
+                let data = self.data.iter().zip(other.data.iter())
+                    .map(|(a, b)| a * (1.0 - t) + b * t)
+                    .collect();
+                Ok(self._with_data(data))
+            }
 
-#[cfg(test)]
-mod tests {
-    use super::{Channel, Image};
-    // TODO: Move these tests and Image, Channel and ImagaData into separate module
-    #[test]
-    fn channel_capacity() {
-        let new_channel = Channel::new(0, 10);
-        assert_eq!(new_channel.capacity(), 10);
+            /// Blend towards `other`, using the matching value in `weights` as `t` for each value
+            pub fn lerp_masked(&self, other: &Channel<$t>, weights: &Channel<$t>) -> Result<Channel<$t>, ChannelError> {
+                if other.len() != self.len() {
+                    return Err(ChannelError::LengthMismatch { expected: self.len(), got: other.len() })
+                }
+                if weights.len() != self.len() {
+                    return Err(ChannelError::LengthMismatch { expected: self.len(), got: weights.len() })
+                }
+                let data = self.data.iter().zip(other.data.iter()).zip(weights.data.iter())
+                    .map(|((a, b), t)| a * (1.0 - t) + b * t)
+                    .collect();
+                Ok(self._with_data(data))
+            }
+        }
     }
+}
 
-    #[test]
-    fn channel_len() {
-        let new_channel = Channel::new(0, 10);
-        assert_eq!(new_channel.len(), 10);
+channel_float!(f32);
+channel_float!(f64);
+
+impl<T: Clone + Debug + Copy> Channel<T> {
+    /// Replace every value with the ones in `src`, which must be as long as the channel
+    pub fn copy_from_slice(&mut self, src: &[T]) -> Result<(), LengthMismatch> {
+        if src.len() != self.len() {
+            return Err(LengthMismatch { expected: self.len(), got: src.len() })
+        }
+        self.data_mut().copy_from_slice(src);
+        let len = self.len();
+        self.mark_dirty(0..len);
+        Ok(())
     }
+}
 
-    #[test]
-    fn channel_resize() {
-        let mut new_channel = Channel::new(0, 10);
-        // Ok, capacity is the amount of data a channel CAN hold WITHOUT REALLOCATING,
-        // but its size is the amount of data it SHOULD hold.
-        // Resizing may change the capacity, but it MUST change the size.
-        new_channel = new_channel.resize(60);
-        assert_eq!(new_channel.len(), 60);
+impl<T: Clone + Debug + Add<Output = T> + Default> Channel<T> {
+    /// Adds up every value. An empty channel sums to `T::default()`
+    pub fn sum(&self) -> T {
+        self.data.iter().cloned().fold(T::default(), |acc, x| acc + x)
     }
 
-    #[test]
+    /// Creates a channel where each value is the sum of all values up to (and including) it here
+    pub fn cumulative_sum(&self) -> Channel<T> {
+        let data = self.data.iter().cloned().scan(T::default(), |acc, x| {
+            *acc = acc.clone() + x;
+            Some(acc.clone())
+        }).collect();
+        self._with_data(data)
+    }
+}
+
+// NOTE: There's no "one" in std, so From<u8> stands in for it
+impl<T: Clone + Debug + Mul<Output = T> + From<u8>> Channel<T> {
+    /// Multiplies every value together. An empty channel gives 1
+    pub fn product(&self) -> T {
+        self.data.iter().cloned().fold(T::from(1), |acc, x| acc * x)
+    }
+}
+
+// NOTE: Only the values are compared, not the defaults
+impl<T: Clone + Debug + PartialEq> PartialEq for Channel<T> {
+    fn eq(&self, other: &Channel<T>) -> bool {
+        self.data == other.data
+    }
+}
+
+/// How many values Debug shows at each end of a channel
+const DEBUG_EDGE: usize = 4;
+
+// Prints as ..., so it can sit in a debug_list
+struct Ellipsis;
+
+impl Debug for Ellipsis {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "...")
+    }
+}
+
+/// Debug-prints a channel or image with all of its data (see `full_debug()`)
+pub struct FullDebug<'a, D: 'a>(&'a D);
+
+impl<T: Clone + Debug> Channel<T> {
+    /// Wraps this channel so Debug prints every value, not just a summary
+    pub fn full_debug(&self) -> FullDebug<Channel<T>> {
+        FullDebug(self)
+    }
+
+    fn fmt_debug(&self, f: &mut Formatter, full: bool) -> Result<(), Error> {
+        f.debug_struct("Channel")
+            .field("len", &self.len())
+            .field("default", &self.default)
+            .field("data", &DebugData(&self.data, full))
+            .finish()
+    }
+}
+
+// The data of a channel, with the middle elided unless `full` is set
+struct DebugData<'a, T: 'a>(&'a [T], bool);
+
+impl<'a, T: Debug + 'a> Debug for DebugData<'a, T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let DebugData(data, full) = *self;
+        if full || data.len() <= 2 * DEBUG_EDGE {
+            f.debug_list().entries(data.iter()).finish()
+        } else {
+            f.debug_list()
+                .entries(data[..DEBUG_EDGE].iter())
+                .entry(&Ellipsis)
+                .entries(data[data.len() - DEBUG_EDGE..].iter())
+                .finish()
+        }
+    }
+}
+
+impl<T: Clone + Debug> Debug for Channel<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.fmt_debug(f, false)
+    }
+}
+
+impl<'a, T: Clone + Debug> Debug for FullDebug<'a, Channel<T>> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.0.fmt_debug(f, true)
+    }
+}
+
+// NOTE that Index implementations PANIC at failure
+impl<T: Clone + Debug> Index<usize> for Channel<T> {
+    type Output = T;
+    fn index(&self, i: usize) -> &T {
+        self.get(i).unwrap()
+    }
+}
+
+// NOTE that Index implementations PANIC at failure
+impl<T: Clone + Debug> IndexMut<usize> for Channel<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        self.get_mut(i).unwrap()
+    }
+}
+
+/// Iterates over the data of a channel
+pub struct ChannelIterator<'a, T: Clone + Debug + 'a> {
+    chan: &'a Channel<T>,
+    at: usize
+}
+
+impl<'a, T: Clone + Debug + 'a> Iterator for ChannelIterator<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.at += 1;
+        if self.at - 1 >= self.chan.len() {
+            None
+        } else {
+            self.chan.get(self.at-1)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.chan.len(), Some(self.chan.len()))
+    }
+}
+impl<'a, T: Clone + Debug + 'a> ExactSizeIterator for ChannelIterator<'a, T> {}
+
+/// The values left over at the end of a channel that don't fill a whole chunk
+pub type Remainder<'a, T> = &'a [T];
+
+/// Iterates over the data of a channel in chunks of an exact size
+pub struct ChannelChunksExact<'a, T: Clone + Debug + 'a> {
+    data: &'a [T],
+    rem: Remainder<'a, T>,
+    size: usize
+}
+
+impl<'a, T: Clone + Debug + 'a> ChannelChunksExact<'a, T> {
+    /// Get the values that were left out because they don't fill a whole chunk
+    pub fn remainder(&self) -> Remainder<'a, T> {
+        self.rem
+    }
+}
+
+impl<'a, T: Clone + Debug + 'a> Iterator for ChannelChunksExact<'a, T> {
+    type Item = &'a [T];
+    fn next(&mut self) -> Option<&'a [T]> {
+        if self.data.len() < self.size {
+            None
+        } else {
+            let (chunk, rest) = self.data.split_at(self.size);
+            self.data = rest;
+            Some(chunk)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.data.len() / self.size;
+        (n, Some(n))
+    }
+}
+impl<'a, T: Clone + Debug + 'a> ExactSizeIterator for ChannelChunksExact<'a, T> {}
+
+
+// TODO A mutable iterator
+
+/// A collection of channels to be interpreted in a certain way.
+/// Like Channel, images are `Send` and `Sync` whenever `T` is
+// NOTE: We DON'T assign a type here. That's MISTER's job...
+// NOTE: Like Channel, Debug summarizes each channel, use full_debug() to see everything
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Image<T: Clone + Debug> {
+    /// NOTE: At this point, we aren't going to even assign a color model, just a configuation of channels
+    channels: Vec<Channel<T>>,
+    /// The size that all channels *must* be.
+    len: usize,
+    /// How many values make up a row. len is always width * height
+    width: usize,
+    /// How many rows there are
+    height: usize,
+}
+
+// Images with 2D geometry store their values like this
+// 0 ----------------> width-1
+// width ------------> 2*width-1
+// ... --------------> ...
+// (height-1)*width -> height*width-1
+// A 1D image is treated as a single row (len x 1)
+impl<T: Clone + Debug> Image<T> {
+    /// Creates a new Image
+    pub fn new(len: usize) -> Image<T> {
+        // NOTE: We start with NO CHANNELS, so something must be done...
+        Image {
+            channels: vec![],
+            len: len,
+            width: len,
+            height: 1
+        }
+    }
+
+    /// Creates a new Image with `width * height` values laid out in rows
+    pub fn new_2d(width: usize, height: usize) -> Image<T> {
+        Image {
+            channels: vec![],
+            len: width * height,
+            width: width,
+            height: height
+        }
+    }
+
+    /// Creates a new Image with one channel for each value in `defaults`
+    pub fn with_channels(len: usize, defaults: &[T]) -> Image<T> {
+        let mut image = Image::new(len);
+        for d in defaults {
+            image.create_channel(d.clone());
+        }
+        image
+    }
+
+    /// Creates a new Image with `channel_count` channels, all using `default`
+    pub fn filled(len: usize, channel_count: usize, default: T) -> Image<T> {
+        Image::with_channels(len, &vec![default; channel_count])
+    }
+
+    // Lay the values out as `width` x `height`, which must match len
+    pub(crate) fn reshaped(mut self, width: usize, height: usize) -> Image<T> {
+        assert_eq!(width * height, self.len, "Image internal error: reshaped to the wrong size");
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Creates an Image from values stored pixel by pixel (c0, c1, ..., cN, c0, c1, ...),
+    /// splitting them into one channel per value in `defaults`
+    pub fn from_interleaved(data: &[T], channel_count: usize, defaults: &[T]) -> Result<Image<T>, ImageError> {
+        if defaults.len() != channel_count {
+            return Err(ImageError::ChannelCountMismatch { expected: channel_count, got: defaults.len() })
+        }
+        if channel_count == 0 || data.len() % channel_count != 0 {
+            return Err(ImageError::NotDivisible { len: data.len(), divisor: channel_count })
+        }
+        let mut image = Image::new(data.len() / channel_count);
+        for (c, default) in defaults.iter().enumerate() {
+            let plane = data.chunks(channel_count).map(|px| px[c].clone()).collect();
+            image.channels.push(Channel::from_vec(default.clone(), plane));
+        }
+        Ok(image)
+    }
+
+    /// Store the values pixel by pixel (c0, c1, ..., cN, c0, c1, ...). This is the inverse of
+    /// `from_interleaved`
+    pub fn to_interleaved(&self) -> Vec<T> {
+        let mut out = Vec::new();
+        self.to_interleaved_into(&mut out);
+        out
+    }
+
+    /// Like `to_interleaved`, but writes into `out` (replacing what was there) so a buffer can
+    /// be reused
+    pub fn to_interleaved_into(&self, out: &mut Vec<T>) {
+        out.clear();
+        out.reserve(self.len * self.count());
+        let planes = self.as_raw_channels();
+        for i in 0..self.len {
+            for p in planes.iter() {
+                out.push(p[i].clone());
+            }
+        }
+    }
+
+    /// Creates a channel
+    // TODO Add specifics (same with Channel::new)
+    pub fn create_channel(&mut self, default: T) {
+        self.channels.push(Channel::new(default, self.len))
+    }
+
+    /// Appends a channel built elsewhere, returning its index.
+    /// The channel must be as long as the image
+    pub fn add_channel(&mut self, chan: Channel<T>) -> Result<usize, ImageError> {
+        if chan.len() != self.len {
+            return Err(ImageError::LengthMismatch { expected: self.len, got: chan.len() })
+        }
+        if let Some(ref label) = chan.label {
+            self.release_label(label);
+        }
+        self.channels.push(chan);
+        Ok(self.count() - 1)
+    }
+
+    /// Appends a copy of the channel at index `src`, returning the index of the copy.
+    /// The copy has no label
+    pub fn clone_channel(&mut self, src: usize) -> Result<usize, ImageError> {
+        let mut copy = self.try_channel(src)?.clone();
+        copy.label = None;
+        self.channels.push(copy);
+        Ok(self.count() - 1)
+    }
+
+    /// Copies the channels at `indices`, in that order, into a new image of the same size.
+    /// An index can be given more than once, but only its first copy keeps the label
+    pub fn select_channels(&self, indices: &[usize]) -> Result<Image<T>, ImageError> {
+        let mut channels: Vec<Channel<T>> = Vec::with_capacity(indices.len());
+        for (n, &i) in indices.iter().enumerate() {
+            let mut copy = self.try_channel(i)?.clone();
+            if indices[..n].contains(&i) {
+                copy.label = None;
+            }
+            channels.push(copy);
+        }
+        Ok(Image {
+            channels: channels,
+            len: self.len,
+            width: self.width,
+            height: self.height
+        })
+    }
+
+    // TODO: Bounds-checking
+    /// Access channel at index `i`
+    pub fn channel(&self, i: usize) -> Option<&Channel<T>> {
+        self.channels.get(i)
+    }
+
+    /// Access channel at index `i` mutably
+    pub fn channel_mut(&mut self, i: usize) -> Option<&mut Channel<T>> {
+        self.channels.get_mut(i)
+    }
+
+    /// Access channel at index `i`, reporting an error if it doesn't exist
+    pub fn try_channel(&self, i: usize) -> Result<&Channel<T>, OutOfBoundsError> {
+        let len = self.count();
+        self.channels.get(i).ok_or(OutOfBoundsError { index: i, len: len })
+    }
+
+    /// Access channel at index `i` mutably, reporting an error if it doesn't exist
+    pub fn try_channel_mut(&mut self, i: usize) -> Result<&mut Channel<T>, OutOfBoundsError> {
+        let len = self.count();
+        self.channels.get_mut(i).ok_or(OutOfBoundsError { index: i, len: len })
+    }
+
+    /// Remove every channel for which `f(index, channel)` returns false
+    pub fn retain_channels<F: Fn(usize, &Channel<T>) -> bool>(&mut self, f: F) {
+        let mut i = 0;
+        self.channels.retain(|c| {
+            i += 1;
+            f(i - 1, c)
+        });
+    }
+
+    /// Name the channel at index `i`. Labels are unique, so if another channel already has
+    /// this label, it loses it (the last channel labelled wins)
+    pub fn set_channel_label<S: Into<String>>(&mut self, i: usize, label: S) -> Result<(), ImageError> {
+        if i >= self.count() {
+            return Err(ImageError::OutOfBounds { index: i, len: self.count() })
+        }
+        let label = label.into();
+        self.release_label(&label);
+        self.channels[i].label = Some(label);
+        Ok(())
+    }
+
+    /// Find the channel labelled `label`
+    pub fn channel_by_label(&self, label: &str) -> Option<&Channel<T>> {
+        self.channels.iter().find(|c| c.label() == Some(label))
+    }
+
+    /// Get the label of the channel at index `i`, if it has one
+    pub fn label_of(&self, i: usize) -> Option<&str> {
+        self.channels.get(i).and_then(|c| c.label())
+    }
+
+    // Remove `label` from any channel that has it
+    fn release_label(&mut self, label: &str) {
+        for c in self.channels.iter_mut() {
+            if c.label() == Some(label) {
+                c.label = None;
+            }
+        }
+    }
+
+    /// Exchange the channels at indices `a` and `b`. Only the channels move, not their data
+    pub fn swap_channels(&mut self, a: usize, b: usize) -> Result<(), ImageError> {
+        for &i in &[a, b] {
+            if i >= self.count() {
+                return Err(ImageError::OutOfBounds { index: i, len: self.count() })
+            }
+        }
+        self.channels.swap(a, b);
+        Ok(())
+    }
+
+    /// Rearrange the channels so that channel `i` is the one that was at `order[i]`.
+    /// `order` must list every channel index exactly once
+    pub fn reorder_channels(&mut self, order: &[usize]) -> Result<(), ImageError> {
+        if order.len() != self.count() {
+            return Err(ImageError::ChannelCountMismatch { expected: self.count(), got: order.len() })
+        }
+        let mut seen = vec![false; self.count()];
+        for &i in order {
+            if i >= self.count() {
+                return Err(ImageError::OutOfBounds { index: i, len: self.count() })
+            }
+            if seen[i] {
+                return Err(ImageError::DuplicateChannel { index: i })
+            }
+            seen[i] = true;
+        }
+        let mut old: Vec<Option<Channel<T>>> = self.channels.drain(..).map(Some).collect();
+        self.channels = order.iter().map(|&i| old[i].take().unwrap()).collect();
+        Ok(())
+    }
+
+    /// Iterate over the channels in order
+    pub fn channels(&self) -> slice::Iter<Channel<T>> {
+        self.channels.iter()
+    }
+
+    /// Iterate over the channels mutably, in order
+    pub fn channels_mut(&mut self) -> slice::IterMut<Channel<T>> {
+        self.channels.iter_mut()
+    }
+
+    /// Iterate over the pixels, giving the value of every channel (in order) at each index
+    pub fn pixel_iter(&self) -> PixelIter<T> {
+        PixelIter {
+            planes: self.as_raw_channels(),
+            at: 0,
+            len: self.len
+        }
+    }
+
+    /// Iterate over the pixels mutably, giving the value of every channel (in order) at each index
+    pub fn pixel_iter_mut(&mut self) -> PixelIterMut<T> {
+        let len = self.len;
+        let planes = self.channels.iter_mut().map(|c| {
+            // NOTE: We can't know which values will change, so assume they all do
+            c.mark_dirty(0..len);
+            c.data_mut().iter_mut()
+        }).collect();
+        PixelIterMut {
+            planes: planes,
+            left: len
+        }
+    }
+
+    /// Borrow the values of every channel, in channel order, without copying them
+    pub fn as_raw_channels(&self) -> Vec<&[T]> {
+        self.channels.iter().map(|c| c.as_raw()).collect()
+    }
+
+    /// Get the number of channels
+    pub fn count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Get the length of image, which is the length of each channel
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the width of the image (its length, for 1D images)
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image (1, for 1D images)
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the index of the value at (x, y), or None if that's outside the image
+    pub fn index_of(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    /// Retrieve the value at (x, y) of channel `channel`
+    pub fn get_2d(&self, channel: usize, x: usize, y: usize) -> Option<&T> {
+        self.index_of(x, y).and_then(|i| self.channels.get(channel).and_then(|c| c.get(i)))
+    }
+
+    /// Change the value at (x, y) of channel `channel` to `value`
+    pub fn set_2d(&mut self, channel: usize, x: usize, y: usize, value: T) -> Result<(), ImageError> {
+        let i = self.index_of(x, y).ok_or(ImageError::RegionOutOfBounds { x: x, y: y, width: 1, height: 1 })?;
+        self.try_channel_mut(channel)?.write(i, value);
+        Ok(())
+    }
+
+    /// Copy the `w` by `h` rectangle at (`x`, `y`) of every channel into a new image
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<Image<T>, ImageError> {
+        let fits = |start: usize, len: usize, max: usize| start.checked_add(len).map_or(false, |end| end <= max);
+        if !fits(x, w, self.width) || !fits(y, h, self.height) {
+            return Err(ImageError::RegionOutOfBounds { x: x, y: y, width: w, height: h })
+        }
+        let mut image = Image::new_2d(w, h);
+        for c in self.channels.iter() {
+            let mut data = Vec::with_capacity(w * h);
+            for row in y..y+h {
+                let start = row * self.width + x;
+                data.extend_from_slice(&c.data[start..start+w]);
+            }
+            image.channels.push(c._with_data_and_label(data));
+        }
+        Ok(image)
+    }
+
+    /// Swap rows and columns of every channel, moving each value at (x, y) to (y, x)
+    // NOTE Can't fail at the moment, but returns a Result to match crop
+    pub fn transpose(&self) -> Result<Image<T>, ImageError> {
+        let mut image = Image::new_2d(self.height, self.width);
+        for c in self.channels.iter() {
+            let mut data = Vec::with_capacity(self.len);
+            for x in 0..self.width {
+                for y in 0..self.height {
+                    data.push(c.data[y * self.width + x].clone());
+                }
+            }
+            image.channels.push(c._with_data_and_label(data));
+        }
+        Ok(image)
+    }
+
+    /// Get the value at index `i` from every channel, in channel order
+    pub fn pixel_values(&self, i: usize) -> Option<Vec<T>> {
+        if i >= self.len {
+            return None
+        }
+        self.channels.iter().map(|c| c.get(i).cloned()).collect()
+    }
+
+    /// Write one value into each channel at index `i`, in channel order
+    pub fn set_pixel_values(&mut self, i: usize, values: &[T]) -> Result<(), ImageError> {
+        if values.len() != self.count() {
+            return Err(ImageError::ChannelCountMismatch { expected: self.count(), got: values.len() })
+        }
+        if i >= self.len {
+            return Err(ImageError::OutOfBounds { index: i, len: self.len })
+        }
+        for (c, v) in self.channels.iter_mut().zip(values.iter()) {
+            c.write(i, v.clone());
+        }
+        Ok(())
+    }
+
+    /// Get the range of indices written to in any channel since the last call,
+    /// and start tracking anew in every channel
+    pub fn take_dirty(&mut self) -> Option<Range<usize>> {
+        let mut dirty: Option<Range<usize>> = None;
+        for d in self.channels.iter_mut().filter_map(|c| c.take_dirty()) {
+            dirty = Some(match dirty {
+                Some(a) => a.start.min(d.start)..a.end.max(d.end),
+                None => d
+            });
+        }
+        dirty
+    }
+
+    /// Break the image up into one single-channel image per channel
+    pub fn split(self) -> Vec<Image<T>> {
+        let (len, width, height) = (self.len, self.width, self.height);
+        self.channels.into_iter().map(|c| {
+            Image {
+                channels: vec![c],
+                len: len,
+                width: width,
+                height: height
+            }
+        }).collect()
+    }
+
+    /// Append the channels of `other` to this image. This is the inverse of `split`.
+    /// Labels from `other` win over the same labels here. When both images are 2D, they must
+    /// also have the same width and height
+    pub fn merge(&mut self, other: Image<T>) -> Result<(), ImageError> {
+        if other.len() != self.len() {
+            return Err(ImageError::LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        // Same length and width means the same height too
+        if self.height > 1 && other.height > 1 && other.width != self.width {
+            return Err(ImageError::LengthMismatch { expected: self.width, got: other.width })
+        }
+        for label in other.channels.iter().filter_map(|c| c.label.as_ref()) {
+            self.release_label(label);
+        }
+        self.channels.extend(other.channels);
+        Ok(())
+    }
+
+    /// Add the values of `other` to the end of every channel, in channel order. Images with the
+    /// same width are stacked as rows; otherwise both must be 1D and the result stays 1D.
+    /// Appending an empty image changes nothing, and appending to one takes the layout of `other`
+    pub fn append(&mut self, other: &Image<T>) -> Result<(), ImageError> {
+        if other.count() != self.count() {
+            return Err(ImageError::ChannelCountMismatch { expected: self.count(), got: other.count() })
+        }
+        if other.len == 0 {
+            return Ok(())
+        }
+        if self.len == 0 {
+            self.width = other.width;
+            self.height = other.height;
+        } else if other.width == self.width {
+            self.height += other.height;
+        } else if self.height == 1 && other.height == 1 {
+            self.width += other.width;
+        } else {
+            return Err(ImageError::WidthMismatch { expected: self.width, got: other.width })
+        }
+        let old_len = self.len;
+        self.len += other.len;
+        for (c, o) in self.channels.iter_mut().zip(other.channels.iter()) {
+            c.data_mut().extend_from_slice(&o.data);
+            c.mark_dirty(old_len..old_len + o.len());
+        }
+        Ok(())
+    }
+
+    /// Join single-channel images (like those from `split`) into one image, in order. Every
+    /// image must have exactly one channel and the same length. The first image sets the width
+    /// and height, and an empty list gives an empty image
+    pub fn merge_all(images: Vec<Image<T>>) -> Result<Image<T>, ImageError> {
+        let mut merged: Option<Image<T>> = None;
+        for image in images {
+            if image.count() != 1 {
+                return Err(ImageError::ChannelCountMismatch { expected: 1, got: image.count() })
+            }
+            merged = Some(match merged {
+                Some(mut m) => {
+                    m.merge(image)?;
+                    m
+                }
+                None => image
+            });
+        }
+        Ok(merged.unwrap_or_else(|| Image::new(0)))
+    }
+
+    /// Run `f` on every value of this image alongside the matching value of `other`, which must
+    /// have the same length and channel count. `f` also gets the channel index
+    pub fn zip_apply<F: FnMut(usize, &mut T, &T)>(&mut self, other: &Image<T>, mut f: F) -> Result<(), ImageError> {
+        if other.len() != self.len() {
+            return Err(ImageError::LengthMismatch { expected: self.len(), got: other.len() })
+        }
+        if other.count() != self.count() {
+            return Err(ImageError::ChannelCountMismatch { expected: self.count(), got: other.count() })
+        }
+        for (i, (c, o)) in self.channels.iter_mut().zip(other.channels.iter()).enumerate() {
+            c.apply_indexed(|j, x| f(i, x, &o.data[j]));
+        }
+        Ok(())
+    }
+
+    /// Convert every value (and every channel default) with `f`, which also gets the channel
+    /// index. The size and labels are kept
+    pub fn map<U: Clone + Debug, F: FnMut(usize, &T) -> U>(&self, mut f: F) -> Image<U> {
+        let channels = self.channels.iter().enumerate().map(|(i, c)| {
+            let mut chan = Channel::from_vec(f(i, &c.default), c.data.iter().map(|v| f(i, v)).collect());
+            chan.label = c.label.clone();
+            chan
+        }).collect();
+        Image {
+            channels: channels,
+            len: self.len,
+            width: self.width,
+            height: self.height
+        }
+    }
+
+    /// Reset every channel to its default, keeping the size and channel count
+    pub fn clear(&mut self) {
+        for c in self.channels.iter_mut() {
+            c.reset();
+        }
+    }
+
+    /// Set every value of each channel to the matching value in `values`, in channel order
+    pub fn fill_all(&mut self, values: &[T]) -> Result<(), ImageError> {
+        if values.len() != self.count() {
+            return Err(ImageError::ChannelCountMismatch { expected: self.count(), got: values.len() })
+        }
+        for (c, v) in self.channels.iter_mut().zip(values.iter()) {
+            c.fill(v.clone());
+        }
+        Ok(())
+    }
+
+    /// Set every value of channel `i` to `value`
+    pub fn fill_channel(&mut self, i: usize, value: T) -> Result<(), ImageError> {
+        self.try_channel_mut(i)?.fill(value);
+        Ok(())
+    }
+
+    /// Remove every pixel (from all channels at once) whose index doesn't pass `f`.
+    /// The image is treated as 1D afterwards
+    pub fn retain_pixels<F: Fn(usize) -> bool>(&mut self, f: F) {
+        let keep: Vec<usize> = (0..self.len).filter(|&i| f(i)).collect();
+        for c in self.channels.iter_mut() {
+            let data = keep.iter().map(|&i| c.data[i].clone()).collect();
+            c.replace_data(data);
+        }
+        self.len = keep.len();
+        self.width = keep.len();
+        self.height = 1;
+    }
+
+    /// Resize image to length `new_len`. The image is treated as 1D afterwards.
+    // NOTE This truncates or pads the flat values, so it scrambles 2D images. Use resize_2d there
+    pub fn resize(&mut self, new_len: usize) {
+        self.len = new_len;
+        self.width = new_len;
+        self.height = 1;
+        for c in self.channels_mut() {
+            c._resize(new_len);
+        }
+    }
+
+    /// Resize image to `new_w` by `new_h`, keeping every value at the same (x, y). Rows are
+    /// cropped or padded on the right, and rows are added or removed at the bottom, using the
+    /// default of each channel
+    pub fn resize_2d(&mut self, new_w: usize, new_h: usize) {
+        let (w, rows) = (self.width, self.height.min(new_h));
+        for c in self.channels_mut() {
+            let mut data = Vec::with_capacity(new_w * new_h);
+            for y in 0..rows {
+                let row = &c.data[y * w..(y + 1) * w];
+                data.extend_from_slice(&row[..w.min(new_w)]);
+                data.extend(iter::repeat(c.default.clone()).take(new_w.saturating_sub(w)));
+            }
+            data.extend(iter::repeat(c.default.clone()).take(new_w * (new_h - rows)));
+            c.replace_data(data);
+        }
+        self.len = new_w * new_h;
+        self.width = new_w;
+        self.height = new_h;
+    }
+}
+
+/// Join `b` onto the end of `a` as a new image, like `Image::append`
+pub fn concat<T: Clone + Debug>(a: &Image<T>, b: &Image<T>) -> Result<Image<T>, ImageError> {
+    let mut image = a.clone();
+    image.append(b)?;
+    Ok(image)
+}
+
+// Deserialization goes through this, so the channels can be checked against len
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawImage<T: Clone + Debug> {
+    channels: Vec<Channel<T>>,
+    len: usize,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Clone + Debug + Deserialize<'de>> Deserialize<'de> for Image<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Image<T>, D::Error> {
+        use serde::de::Error as DeError;
+
+        let raw = RawImage::deserialize(deserializer)?;
+        if raw.width * raw.height != raw.len {
+            return Err(D::Error::custom(format!("image is {}x{}, but has length {}", raw.width, raw.height, raw.len)))
+        }
+        if let Some(c) = raw.channels.iter().find(|c| c.len() != raw.len) {
+            return Err(D::Error::custom(ImageError::LengthMismatch { expected: raw.len, got: c.len() }))
+        }
+        Ok(Image {
+            channels: raw.channels,
+            len: raw.len,
+            width: raw.width,
+            height: raw.height
+        })
+    }
+}
+
+impl<T: Clone + Debug + PartialEq> PartialEq for Image<T> {
+    fn eq(&self, other: &Image<T>) -> bool {
+        self.len == other.len && self.width == other.width && self.height == other.height
+            && self.count() == other.count() && self.channels == other.channels
+    }
+}
+
+impl<T: Clone + Debug> Image<T> {
+    /// Wraps this image so Debug prints every value of every channel, not just a summary
+    pub fn full_debug(&self) -> FullDebug<Image<T>> {
+        FullDebug(self)
+    }
+
+    fn fmt_debug(&self, f: &mut Formatter, full: bool) -> Result<(), Error> {
+        let channels: Vec<_> = self.channels.iter().map(|c| DebugChannel(c, full)).collect();
+        f.debug_struct("Image")
+            .field("count", &self.count())
+            .field("len", &self.len)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("channels", &channels)
+            .finish()
+    }
+}
+
+// A channel printed in full or summarized
+struct DebugChannel<'a, T: Clone + Debug + 'a>(&'a Channel<T>, bool);
+
+impl<'a, T: Clone + Debug + 'a> Debug for DebugChannel<'a, T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.0.fmt_debug(f, self.1)
+    }
+}
+
+impl<T: Clone + Debug> Debug for Image<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.fmt_debug(f, false)
+    }
+}
+
+impl<'a, T: Clone + Debug> Debug for FullDebug<'a, Image<T>> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.0.fmt_debug(f, true)
+    }
+}
+
+// NOTE Index impl PANIC at failure
+impl<T: Clone + Debug> Index<usize> for Image<T> {
+    type Output = Channel<T>;
+    fn index(&self, i: usize) -> &Channel<T> {
+        self.channel(i).unwrap()
+    }
+}
+
+impl<T: Clone + Debug> IndexMut<usize> for Image<T> {
+    fn index_mut(&mut self, i: usize) -> &mut Channel<T> {
+        self.channel_mut(i).unwrap()
+    }
+}
+
+
+/// Iterates over the pixels of an image, giving a value from each channel
+pub struct PixelIter<'a, T: Clone + Debug + 'a> {
+    planes: Vec<&'a [T]>,
+    at: usize,
+    len: usize
+}
+
+impl<'a, T: Clone + Debug + 'a> Iterator for PixelIter<'a, T> {
+    type Item = Vec<&'a T>;
+    fn next(&mut self) -> Option<Vec<&'a T>> {
+        if self.at >= self.len {
+            return None
+        }
+        self.at += 1;
+        Some(self.planes.iter().map(|p| &p[self.at - 1]).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.at;
+        (n, Some(n))
+    }
+}
+impl<'a, T: Clone + Debug + 'a> ExactSizeIterator for PixelIter<'a, T> {}
+
+/// Iterates over the pixels of an image mutably, giving a value from each channel
+pub struct PixelIterMut<'a, T: Clone + Debug + 'a> {
+    planes: Vec<slice::IterMut<'a, T>>,
+    left: usize
+}
+
+impl<'a, T: Clone + Debug + 'a> Iterator for PixelIterMut<'a, T> {
+    type Item = Vec<&'a mut T>;
+    fn next(&mut self) -> Option<Vec<&'a mut T>> {
+        if self.left == 0 {
+            return None
+        }
+        self.left -= 1;
+        // Every plane has len values, so none can run out early
+        Some(self.planes.iter_mut().map(|p| p.next().unwrap()).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+impl<'a, T: Clone + Debug + 'a> ExactSizeIterator for PixelIterMut<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Channel, ChannelError, Image, ImageError, LengthMismatch, OutOfBoundsError, WindowFunction, concat};
+    // TODO: Move these tests and Image, Channel and ImagaData into separate module
+    #[test]
+    fn channel_capacity() {
+        let new_channel = Channel::new(0, 10);
+        assert_eq!(new_channel.capacity(), 10);
+    }
+
+    #[test]
+    fn channel_len() {
+        let new_channel = Channel::new(0, 10);
+        assert_eq!(new_channel.len(), 10);
+    }
+
+    #[test]
+    fn channel_resize() {
+        let mut new_channel = Channel::new(0, 10);
+        // Ok, capacity is the amount of data a channel CAN hold WITHOUT REALLOCATING,
+        // but its size is the amount of data it SHOULD hold.
+        // Resizing may change the capacity, but it MUST change the size.
+        new_channel = new_channel.resize(60);
+        assert_eq!(new_channel.len(), 60);
+    }
+
+    #[test]
     fn channel_write() {
         let mut new_channel = Channel::new(0u8, 10);
-        // So writing is simple, but we can only do it 1 item at a time.
-        // TODO: Make it so that blocks can be written to a channel
-        let len = new_channel.len();
-        new_channel.write(4, 21);
-        assert_eq!(len, new_channel.len()); // length cannot change with write!
-        assert_eq!(new_channel.iter().cloned().collect::<Vec<_>>(), vec![0,0,0,0,21,0,0,0,0,0]);
+        // So writing is simple, but we can only do it 1 item at a time.
+        // TODO: Make it so that blocks can be written to a channel
+        let len = new_channel.len();
+        new_channel.write(4, 21);
+        assert_eq!(len, new_channel.len()); // length cannot change with write!
+        assert_eq!(new_channel.iter().cloned().collect::<Vec<_>>(), vec![0,0,0,0,21,0,0,0,0,0]);
+    }
+
+    #[test]
+    fn channel_getting() {
+        let mut new_channel = Channel::new(0u8, 10);
+        // So writing is simple, but we can only do it 1 item at a time.
+        // TODO: Make it so that blocks can be written to a channel
+        let len = new_channel.len();
+        new_channel.write(4, 21);
+        assert_eq!(len, new_channel.len()); // length cannot change with write!
+        assert_eq!(new_channel.get(4).cloned(), Some(21));
+        new_channel.get_mut(4).map(|x| *x = 42);
+        assert_eq!(new_channel.get(4).cloned(), Some(42));
+    }
+
+    #[test]
+    fn channel_to_vec() {
+        let mut new_channel = Channel::new(0u8, 5);
+        new_channel.write(2, 21);
+        let copied = new_channel.to_vec();
+        assert_eq!(copied, vec![0,0,21,0,0]);
+        assert_eq!(new_channel.into_vec(), copied);
+        assert_eq!(Channel::new(0u8, 0).into_vec(), vec![]);
+    }
+
+    #[test]
+    fn channel_convert() {
+        let bytes = [0u8, 1, 127, 128, 254, 255];
+        let floats = Channel::from_u8_scaled(&bytes);
+        assert_eq!(floats.get(0).cloned(), Some(0.0));
+        assert_eq!(floats.get(5).cloned(), Some(1.0));
+        assert_eq!(floats.to_u8().to_vec(), bytes.to_vec());
+
+        let words = [0u16, 1, 32767, 65535];
+        assert_eq!(Channel::from_u16_scaled(&words).to_u16().to_vec(), words.to_vec());
+
+        let mut out_of_range = Channel::new(0.5f32, 3);
+        out_of_range.write(0, -0.5);
+        out_of_range.write(2, 1.5);
+        assert_eq!(out_of_range.to_u8().to_vec(), vec![0, 128, 255]);
+        assert_eq!(out_of_range.to_u16().to_vec(), vec![0, 32768, 65535]);
+    }
+
+    #[test]
+    fn channel_as_raw() {
+        let mut chan = Channel::new(0u8, 3);
+        chan.write(1, 4);
+        assert_eq!(chan.as_raw(), &[0, 4, 0]);
+
+        let mut new_data = Image::new(2);
+        new_data.create_channel(1u8);
+        new_data.create_channel(2u8);
+        assert_eq!(new_data.as_raw_channels(), vec![&[1, 1][..], &[2, 2][..]]);
+    }
+
+    #[test]
+    fn channel_send_sync() {
+        use format::RgbaImage;
+        use std::thread;
+
+        fn assert_send_sync<S: Send + Sync>() {}
+        assert_send_sync::<Channel<f32>>();
+        assert_send_sync::<Image<u8>>();
+        assert_send_sync::<RgbaImage>();
+
+        // A clone sent to another thread still shares data until written to
+        let chan = Channel::new(1u8, 4);
+        let shared = chan.clone();
+        let sum = thread::spawn(move || shared.iter().map(|x| *x as u32).sum::<u32>()).join().unwrap();
+        assert_eq!(sum, 4);
+    }
+
+    #[test]
+    fn channel_copy_on_write() {
+        let mut original = Channel::new(0u8, 5);
+        original.write(1, 21);
+        let ptr = original.data.as_ptr();
+        original.write(2, 22);
+        // Unshared writes happen in place
+        assert_eq!(original.data.as_ptr(), ptr);
+        assert!(!original.is_shared());
+
+        let mut snapshot = original.clone();
+        assert!(original.is_shared());
+        assert_eq!(snapshot.data.as_ptr(), ptr);
+        snapshot.write(1, 42);
+        assert!(!original.is_shared());
+        assert_eq!(original.to_vec(), vec![0,21,22,0,0]);
+        assert_eq!(snapshot.to_vec(), vec![0,42,22,0,0]);
+        assert_eq!(original.data.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn channel_apply_indexed() {
+        let mut new_channel = Channel::new(1usize, 5);
+        new_channel.apply_indexed(|i, x| *x += i * 10);
+        assert_eq!(new_channel.to_vec(), vec![1,11,21,31,41]);
+        assert_eq!(new_channel.take_dirty(), Some(0..5));
+    }
+
+    #[test]
+    fn channel_dirty_tracking() {
+        let mut new_channel = Channel::new(0u8, 10);
+        assert_eq!(new_channel.take_dirty(), None);
+        new_channel.write(6, 1);
+        new_channel.write(2, 1);
+        new_channel[4] = 1;
+        assert_eq!(new_channel.take_dirty(), Some(2..7));
+        assert_eq!(new_channel.take_dirty(), None);
+        new_channel.reset();
+        assert_eq!(new_channel.take_dirty(), Some(0..10));
+        new_channel = new_channel.resize(12);
+        assert_eq!(new_channel.take_dirty(), Some(10..12));
     }
 
     #[test]
-    fn channel_getting() {
+    fn channel_bytes() {
+        let mut bytes = Channel::new(0u8, 3);
+        bytes.write(1, 200);
+        assert_eq!(bytes.to_bytes(), vec![0, 200, 0]);
+        assert_eq!(Channel::from_bytes(&bytes.to_bytes(), 0u8).unwrap(), bytes);
+
+        let mut words = Channel::new(0u16, 2);
+        words.write(0, 0x1234);
+        assert_eq!(words.to_bytes(), vec![0x34, 0x12, 0, 0]);
+        assert_eq!(Channel::from_bytes(&words.to_bytes(), 0u16).unwrap(), words);
+        assert_eq!(Channel::<u16>::from_bytes(&[0, 1, 2], 0).err(), Some(ChannelError::NotDivisible { len: 3, divisor: 2 }));
+
+        let mut floats = Channel::new(0.5f32, 3);
+        floats.write(0, ::std::f32::NAN);
+        floats.write(2, -1.25);
+        let read = Channel::<f32>::from_bytes(&floats.to_bytes(), 0.0).unwrap();
+        // NaN != NaN, so compare the bits
+        assert_eq!(read.iter().map(|x| x.to_bits()).collect::<Vec<_>>(), floats.iter().map(|x| x.to_bits()).collect::<Vec<_>>());
+        assert!(Channel::<f32>::from_bytes(&[0; 6], 0.0).is_err());
+
+        let mut doubles = Channel::new(0.0f64, 2);
+        doubles.write(1, 1e300);
+        assert_eq!(doubles.to_bytes().len(), 16);
+        assert_eq!(Channel::from_bytes(&doubles.to_bytes(), 0.0f64).unwrap(), doubles);
+        assert_eq!(Channel::<f64>::from_bytes(&[], 0.0).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn channel_lerp() {
+        let mut a = Channel::new(0.0f32, 3);
+        let mut b = Channel::new(1.0f32, 3);
+        a.write(2, 0.3);
+        b.write(0, 0.7);
+        assert_eq!(a.lerp(&b, 0.0).unwrap(), a);
+        assert_eq!(a.lerp(&b, 1.0).unwrap(), b);
+        assert_eq!(a.lerp(&b, 0.5).unwrap().to_vec(), vec![0.35, 0.5, 0.65]);
+        assert_eq!(a.lerp(&Channel::new(0.0, 2), 0.5).err(), Some(ChannelError::LengthMismatch { expected: 3, got: 2 }));
+
+        let mut weights = Channel::new(0.0f32, 3);
+        weights.write(1, 1.0);
+        weights.write(2, 0.5);
+        assert_eq!(a.lerp_masked(&b, &weights).unwrap().to_vec(), vec![0.0, 1.0, 0.65]);
+        assert!(a.lerp_masked(&b, &Channel::new(0.0, 4)).is_err());
+        assert!(Channel::new(0.0f64, 2).lerp(&Channel::new(1.0, 2), 0.25).unwrap().iter().all(|x| *x == 0.25));
+    }
+
+    #[test]
+    fn channel_sum() {
+        let mut chan = Channel::new(0u32, 4);
+        chan.apply_indexed(|i, x| *x = i as u32 + 1);
+        assert_eq!(chan.sum(), 10);
+        assert_eq!(Channel::new(0.25f32, 4).sum(), 1.0);
+        assert_eq!(Channel::<f32>::new(1.0, 0).sum(), 0.0);
+    }
+
+    #[test]
+    fn channel_cumulative_sum() {
+        let mut chan = Channel::new(0u32, 4);
+        chan.apply_indexed(|i, x| *x = i as u32 + 1);
+        let sums = chan.cumulative_sum();
+        assert_eq!(sums.to_vec(), vec![1, 3, 6, 10]);
+        assert_eq!(sums[3], chan.sum());
+        assert_eq!(Channel::<u8>::new(1, 0).cumulative_sum().len(), 0);
+    }
+
+    #[test]
+    fn channel_product() {
+        let mut chan = Channel::new(0u32, 4);
+        chan.apply_indexed(|i, x| *x = i as u32 + 1);
+        assert_eq!(chan.product(), 24);
+        assert_eq!(Channel::new(0.5f32, 3).product(), 0.125);
+        assert_eq!(Channel::<f32>::new(0.0, 0).product(), 1.0);
+    }
+
+    #[test]
+    fn channel_checked_writes() {
+        let mut chan = Channel::new(0u8, 4);
+        assert_eq!(chan.try_write(1, 5), Ok(()));
+        assert_eq!(chan.try_write(4, 5), Err(ChannelError::OutOfBounds { index: 4, len: 4 }));
+        assert_eq!(chan.write_slice(2, &[7, 8]), Ok(()));
+        assert_eq!(chan.write_slice(3, &[1, 1]), Err(ChannelError::OutOfBounds { index: 4, len: 4 }));
+        assert_eq!(chan.to_vec(), vec![0, 5, 7, 8]);
+
+        let mut copy = Channel::new(1u8, 4);
+        assert_eq!(copy.copy_from(&chan), Ok(()));
+        assert_eq!(copy, chan);
+        assert_eq!(copy.copy_from(&Channel::new(0, 3)), Err(ChannelError::LengthMismatch { expected: 4, got: 3 }));
+
+        let sums = chan.zip_with(&copy, |a, b| *a as u16 + *b as u16).unwrap();
+        assert_eq!(sums.to_vec(), vec![0, 10, 14, 16]);
+        assert!(chan.zip_with(&Channel::new(0u8, 2), |a, b| a + b).is_err());
+    }
+
+    #[test]
+    fn channel_copy_from_slice() {
+        let mut chan = Channel::new(0u16, 3);
+        assert_eq!(chan.copy_from_slice(&[4, 5, 6]), Ok(()));
+        assert_eq!(chan.to_vec(), vec![4, 5, 6]);
+        assert_eq!(chan.take_dirty(), Some(0..3));
+        assert_eq!(chan.copy_from_slice(&[1, 2]), Err(LengthMismatch { expected: 3, got: 2 }));
+        assert_eq!(chan.to_vec(), vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn channel_error_display() {
+        assert_eq!(ChannelError::OutOfBounds { index: 9, len: 4 }.to_string(), "index 9 out of bounds for length 4");
+        assert_eq!(ChannelError::LengthMismatch { expected: 4, got: 3 }.to_string(), "expected length 4, got 3");
+        assert_eq!(ChannelError::from(OutOfBoundsError { index: 2, len: 1 }), ChannelError::OutOfBounds { index: 2, len: 1 });
+    }
+
+    #[test]
+    fn channel_predicates() {
+        let mut new_channel = Channel::new(0u8, 5);
+        new_channel.write(0, 3);
+        new_channel.write(4, 3);
+        assert_eq!(new_channel.position(|x| *x == 3), Some(0));
+        assert_eq!(new_channel.position(|x| *x == 4), None);
+        assert_eq!(new_channel.count_where(|x| *x == 3), 2);
+        assert!(new_channel.any(|x| *x == 3));
+        assert!(!new_channel.all(|x| *x == 3));
+        new_channel.write(0, 0);
+        // Only the last value matches
+        assert_eq!(new_channel.position(|x| *x == 3), Some(4));
+
+        let empty = Channel::new(0u8, 0);
+        assert_eq!(empty.position(|_| true), None);
+        assert_eq!(empty.count_where(|_| true), 0);
+        assert!(!empty.any(|_| true));
+        assert!(empty.all(|_| false));
+    }
+
+    #[test]
+    fn channel_interleave() {
+        let mut red = Channel::new(0u8, 3);
+        let mut green = Channel::new(1u8, 3);
+        red.write(0, 10);
+        green.write(2, 12);
+        assert_eq!(red.interleave(&green).unwrap().to_vec(), vec![10,1,0,1,0,12]);
+        assert_eq!(red.interleave(&Channel::new(0, 2)).err(), Some(LengthMismatch { expected: 3, got: 2 }));
+    }
+
+    #[test]
+    fn channel_deinterleave() {
+        let mut red = Channel::new(0u8, 3);
+        let mut green = Channel::new(1u8, 3);
+        red.write(0, 10);
+        green.write(2, 12);
+        let planes = red.interleave(&green).unwrap().deinterleave(2).unwrap();
+        assert_eq!(planes.len(), 2);
+        assert_eq!(planes[0].to_vec(), red.to_vec());
+        assert_eq!(planes[1].to_vec(), green.to_vec());
+
+        assert_eq!(red.deinterleave(2).err(), Some(ChannelError::NotDivisible { len: 3, divisor: 2 }));
+        assert_eq!(red.deinterleave(0).err(), Some(ChannelError::ZeroStep));
+        assert_eq!(red.deinterleave(1).unwrap(), vec![red.clone()]);
+    }
+
+    #[test]
+    fn channel_downsample_by() {
+        let mut new_channel = Channel::new(0u8, 6);
+        new_channel.apply_indexed(|i, x| *x = i as u8);
+        assert_eq!(new_channel.downsample_by(2).unwrap().to_vec(), vec![0,2,4]);
+        assert_eq!(new_channel.downsample_by(1).unwrap(), new_channel);
+        assert_eq!(new_channel.downsample_by(4).err(), Some(ChannelError::NotDivisible { len: 6, divisor: 4 }));
+        assert!(new_channel.downsample_by(0).is_err());
+    }
+
+    #[test]
+    fn channel_stride_sample() {
+        let mut new_channel = Channel::new(0u8, 7);
+        new_channel.apply_indexed(|i, x| *x = i as u8);
+        assert_eq!(new_channel.stride_sample(1, 0).unwrap(), new_channel);
+        assert_eq!(new_channel.stride_sample(3, 1).unwrap().to_vec(), vec![1,4]);
+        assert_eq!(new_channel.stride_sample(10, 2).unwrap().to_vec(), vec![2]);
+        assert_eq!(new_channel.stride_sample(2, 7).unwrap().len(), 0);
+        assert_eq!(new_channel.stride_sample(0, 0).err(), Some(ChannelError::ZeroStep));
+    }
+
+    #[test]
+    fn channel_upsample_by() {
+        let mut new_channel = Channel::new(0u8, 3);
+        new_channel.apply_indexed(|i, x| *x = i as u8 + 1);
+        assert_eq!(new_channel.upsample_by(3, 0).to_vec(), vec![1,0,0,2,0,0,3,0,0]);
+        assert_eq!(new_channel.upsample_by(1, 0), new_channel);
+        assert_eq!(new_channel.upsample_by(2, 9).downsample_by(2).unwrap(), new_channel);
+        assert_eq!(new_channel.upsample_by(0, 0).len(), 0);
+    }
+
+    #[test]
+    fn channel_gamma() {
+        let mut new_channel = Channel::new(0.5f32, 3);
+        new_channel.write(0, 0.0);
+        new_channel.write(2, 1.0);
+        new_channel.gamma(2.0);
+        assert_eq!(new_channel.to_vec(), vec![0.0, 0.25, 1.0]);
+
+        let mut srgb = Channel::new(0.0f32, 11);
+        srgb.apply_indexed(|i, v| *v = i as f32 / 10.0);
+        let original = srgb.clone();
+        srgb.to_linear();
+        // Middle gray in sRGB is about 21% of the light
+        assert!((srgb[5] - 0.214).abs() < 1e-3);
+        srgb.to_srgb();
+        for i in 0..11 {
+            assert!((srgb[i] - original[i]).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn channel_covariance() {
+        let mut a = Channel::new(0.0f32, 4);
+        a.copy_from_slice(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let mut b = a.clone();
+        b.copy_from_slice(&[2.0, 4.0, 6.0, 8.0]).unwrap();
+        // The variance of a is 1.25
+        assert_eq!(a.covariance(&a), Ok(1.25));
+        assert_eq!(a.covariance(&b), Ok(2.5));
+        b.copy_from_slice(&[4.0, 3.0, 2.0, 1.0]).unwrap();
+        assert_eq!(a.covariance(&b), Ok(-1.25));
+        assert_eq!(a.covariance(&Channel::new(1.0, 4)), Ok(0.0));
+        assert_eq!(a.covariance(&Channel::new(1.0, 2)), Err(LengthMismatch { expected: 4, got: 2 }));
+    }
+
+    #[test]
+    fn channel_psnr() {
+        let a = Channel::new(0.5f32, 4);
+        let mut b = a.clone();
+        assert_eq!(a.psnr(&b), Ok(::std::f32::INFINITY));
+        // An error of 0.1 everywhere gives an MSE of 0.01, so 20 dB
+        b.fill(0.6);
+        assert!((a.psnr(&b).unwrap() - 20.0).abs() < 1e-3);
+        assert_eq!(a.psnr(&Channel::new(0.0, 3)), Err(LengthMismatch { expected: 4, got: 3 }));
+    }
+
+    #[test]
+    fn channel_power_law_transform() {
+        let mut new_channel = Channel::new(0.0f32, 3);
+        new_channel.write_slice(0, &[0.0, 0.25, 1.0]).unwrap();
+        assert_eq!(new_channel.power_law_transform(0.5).unwrap().to_vec(), vec![0.0, 0.5, 1.0]);
+        assert_eq!(new_channel.power_law_transform(2.0).unwrap().to_vec(), vec![0.0, 0.0625, 1.0]);
+        assert_eq!(new_channel.power_law_transform(0.0), Err(ChannelError::NonPositiveExponent));
+        assert_eq!(new_channel.power_law_transform(-1.0), Err(ChannelError::NonPositiveExponent));
+        new_channel.write(1, -0.25);
+        assert_eq!(new_channel.power_law_transform(2.0), Err(ChannelError::NegativeValue { index: 1 }));
+    }
+
+    #[test]
+    fn channel_window_function() {
+        let new_channel = Channel::new(2.0f32, 5);
+        let hann = new_channel.apply_window_function(WindowFunction::Hann);
+        let expected = [0.0, 1.0, 2.0, 1.0, 0.0];
+        assert!(hann.iter().zip(expected.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+        let hamming = new_channel.apply_window_function(WindowFunction::Hamming);
+        assert!((hamming[0] - 0.16).abs() < 1e-5 && (hamming[2] - 2.0).abs() < 1e-5);
+        let blackman = new_channel.apply_window_function(WindowFunction::Blackman);
+        assert!(blackman[0].abs() < 1e-5 && (blackman[2] - 2.0).abs() < 1e-5);
+        assert!((blackman[1] - blackman[3]).abs() < 1e-5);
+        assert_eq!(new_channel.apply_window_function(WindowFunction::Rectangular), new_channel);
+        // A single value has nothing to taper
+        assert_eq!(Channel::new(3.0f32, 1).apply_window_function(WindowFunction::Hann).to_vec(), vec![3.0]);
+    }
+
+    #[test]
+    fn channel_quantize() {
+        let mut new_channel = Channel::new(0.0f32, 11);
+        new_channel.apply_indexed(|i, v| *v = i as f32 / 10.0);
+        let two = new_channel.quantized(2).unwrap();
+        assert!(two.iter().all(|v| *v == 0.0 || *v == 1.0));
+        // Halfway rounds up
+        assert_eq!(two[4], 0.0);
+        assert_eq!(two[5], 1.0);
+        assert_eq!(new_channel.quantized(3).unwrap().to_vec(), vec![0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0]);
+        assert_eq!(new_channel.quantize(1), Err(ChannelError::TooFewLevels { levels: 1 }));
+        new_channel.write(0, -0.5);
+        new_channel.write(10, 2.0);
+        assert_eq!(new_channel.quantize(2), Ok(()));
+        assert_eq!((new_channel[0], new_channel[10]), (0.0, 1.0));
+    }
+
+    #[cfg(feature = "fft")]
+    #[test]
+    fn channel_to_complex() {
+        use num_complex::Complex;
+
+        let mut new_channel = Channel::new(0.5f32, 3);
+        new_channel.write(1, -2.0);
+        let complex = new_channel.to_complex();
+        assert_eq!(complex.to_vec(), vec![Complex::new(0.5, 0.0), Complex::new(-2.0, 0.0), Complex::new(0.5, 0.0)]);
+    }
+
+    #[test]
+    fn channel_chunks_exact() {
         let mut new_channel = Channel::new(0u8, 10);
-        // So writing is simple, but we can only do it 1 item at a time.
-        // TODO: Make it so that blocks can be written to a channel
-        let len = new_channel.len();
-        new_channel.write(4, 21);
-        assert_eq!(len, new_channel.len()); // length cannot change with write!
-        assert_eq!(new_channel.get(4).cloned(), Some(21));
-        new_channel.get_mut(4).map(|x| *x = 42);
-        assert_eq!(new_channel.get(4).cloned(), Some(42));
+        for i in 0..10 {
+            new_channel.write(i, i as u8);
+        }
+        let mut chunks = new_channel.chunks_exact(4);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.next(), Some(&[0, 1, 2, 3][..]));
+        assert_eq!(chunks.next(), Some(&[4, 5, 6, 7][..]));
+        assert_eq!(chunks.next(), None);
+        assert_eq!(chunks.remainder(), &[8, 9]);
+        assert!(new_channel.chunks_exact(5).remainder().is_empty());
     }
 
     #[test]
@@ -293,6 +2273,579 @@ mod tests {
         assert_eq!(new_data.len(), 3);
     }
 
+    #[test]
+    fn imagedata_resize_2d() {
+        let mut new_data = Image::filled(6, 2, 0u8).reshaped(3, 2);
+        new_data[0].write_slice(0, &[1, 2, 3, 4, 5, 6]).unwrap();
+        new_data[1].fill(7);
+        // Growing the width pads each row on the right
+        new_data.resize_2d(4, 2);
+        assert_eq!((new_data.len(), new_data.width(), new_data.height()), (8, 4, 2));
+        assert_eq!(new_data[0].to_vec(), vec![1, 2, 3, 0, 4, 5, 6, 0]);
+        assert_eq!(new_data[1].to_vec(), vec![7, 7, 7, 0, 7, 7, 7, 0]);
+        // Shrinking the height drops the bottom rows
+        new_data.resize_2d(4, 1);
+        assert_eq!(new_data[0].to_vec(), vec![1, 2, 3, 0]);
+        // The top-left corner survives a shrink and regrow
+        new_data.resize_2d(2, 3);
+        assert_eq!(new_data[0].to_vec(), vec![1, 2, 0, 0, 0, 0]);
+        assert_eq!(new_data.get_2d(0, 1, 0), Some(&2));
+        assert_eq!(new_data.get_2d(1, 1, 2), Some(&0));
+    }
+
+    #[test]
+    fn imagedata_from_interleaved() {
+        let new_data = Image::from_interleaved(&[1, 2, 3, 4, 5, 6], 2, &[0, 9]).unwrap();
+        assert_eq!((new_data.count(), new_data.len()), (2, 3));
+        assert_eq!(new_data[0].to_vec(), vec![1, 3, 5]);
+        assert_eq!(new_data[1].to_vec(), vec![2, 4, 6]);
+        let mut cleared = new_data.clone();
+        cleared.clear();
+        assert_eq!(cleared.pixel_values(0), Some(vec![0, 9]));
+
+        // Each pixel comes back out as it went in
+        for &count in &[3usize, 4] {
+            let data: Vec<u8> = (0..count as u8 * 5).collect();
+            let new_data = Image::from_interleaved(&data, count, &vec![0; count]).unwrap();
+            assert_eq!(new_data.len(), 5);
+            assert_eq!(new_data.to_interleaved(), data);
+        }
+
+        assert_eq!(Image::from_interleaved(&[1, 2, 3], 2, &[0, 0]).err(), Some(ImageError::NotDivisible { len: 3, divisor: 2 }));
+        assert_eq!(Image::from_interleaved(&[1, 2], 2, &[0]).err(), Some(ImageError::ChannelCountMismatch { expected: 2, got: 1 }));
+        assert_eq!(Image::from_interleaved(&[], 0, &[0u8; 0]).err(), Some(ImageError::NotDivisible { len: 0, divisor: 0 }));
+    }
+
+    #[test]
+    fn imagedata_to_interleaved() {
+        let mut new_data = Image::new(3);
+        new_data.create_channel(1u8);
+        new_data.create_channel(2u8);
+        new_data.create_channel(3u8);
+        new_data[1].write(2, 7);
+        assert_eq!(new_data.to_interleaved(), vec![1, 2, 3, 1, 2, 3, 1, 7, 3]);
+
+        let mut buffer = vec![9; 20];
+        new_data.to_interleaved_into(&mut buffer);
+        assert_eq!(buffer, new_data.to_interleaved());
+
+        assert!(Image::<u8>::new(0).to_interleaved().is_empty());
+        assert!(Image::<u8>::new(4).to_interleaved().is_empty());
+    }
+
+    #[test]
+    fn imagedata_pixel_iter() {
+        let mut new_data = Image::new(3);
+        new_data.create_channel(0u8);
+        new_data.create_channel(0u8);
+        new_data[0].apply_indexed(|i, x| *x = i as u8);
+        new_data[1].apply_indexed(|i, x| *x = 10 + i as u8);
+
+        let pixels: Vec<Vec<u8>> = new_data.pixel_iter().map(|px| px.into_iter().cloned().collect()).collect();
+        assert_eq!(pixels, vec![vec![0, 10], vec![1, 11], vec![2, 12]]);
+        assert_eq!(new_data.pixel_iter().len(), 3);
+
+        // Swap the two channels at every pixel
+        for mut px in new_data.pixel_iter_mut() {
+            let first = *px[0];
+            *px[0] = *px[1];
+            *px[1] = first;
+        }
+        assert_eq!(new_data[0].to_vec(), vec![10, 11, 12]);
+        assert_eq!(new_data[1].to_vec(), vec![0, 1, 2]);
+        assert_eq!(new_data.take_dirty(), Some(0..3));
+
+        // Images without channels still have pixels, just empty ones
+        assert_eq!(Image::<u8>::new(2).pixel_iter().count(), 2);
+    }
+
+    #[test]
+    fn imagedata_pixel_values() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        new_data[0].write(2, 21);
+        new_data[1].write(2, 22);
+        assert_eq!(new_data.pixel_values(2), Some(vec![21, 22]));
+        assert_eq!(new_data.pixel_values(0), Some(vec![0, 1]));
+        assert_eq!(new_data.pixel_values(5), None);
+    }
+
+    #[test]
+    fn imagedata_set_pixel_values() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        assert_eq!(new_data.set_pixel_values(3, &[9, 8]), Ok(()));
+        assert_eq!(new_data.pixel_values(3), Some(vec![9, 8]));
+        assert_eq!(new_data[0].iter().cloned().collect::<Vec<_>>(), vec![0,0,0,9,0]);
+        assert_eq!(new_data.set_pixel_values(3, &[9]), Err(ImageError::ChannelCountMismatch { expected: 2, got: 1 }));
+        assert_eq!(new_data.set_pixel_values(5, &[9, 8]), Err(ImageError::OutOfBounds { index: 5, len: 5 }));
+    }
+
+    #[test]
+    fn imagedata_add_channel() {
+        let mut new_data = Image::new(3);
+        new_data.create_channel(0u8);
+        assert_eq!(new_data.add_channel(Channel::new(5, 3)), Ok(1));
+        assert_eq!(new_data.count(), 2);
+        assert_eq!(new_data[1].to_vec(), vec![5, 5, 5]);
+        assert_eq!(new_data.add_channel(Channel::new(5, 4)), Err(ImageError::LengthMismatch { expected: 3, got: 4 }));
+        assert_eq!(new_data.count(), 2);
+    }
+
+    #[test]
+    fn imagedata_reorder_channels() {
+        // Stored as BGRA
+        let mut new_data = Image::new(2);
+        for v in &[3u8, 2, 1, 4] {
+            new_data.create_channel(*v);
+        }
+        new_data.reorder_channels(&[2, 1, 0, 3]).unwrap();
+        assert_eq!(new_data.pixel_values(0), Some(vec![1, 2, 3, 4]));
+        new_data.swap_channels(0, 3).unwrap();
+        assert_eq!(new_data.pixel_values(1), Some(vec![4, 2, 3, 1]));
+
+        assert_eq!(new_data.swap_channels(0, 4), Err(ImageError::OutOfBounds { index: 4, len: 4 }));
+        assert_eq!(new_data.reorder_channels(&[0, 1, 2]), Err(ImageError::ChannelCountMismatch { expected: 4, got: 3 }));
+        assert_eq!(new_data.reorder_channels(&[0, 1, 1, 3]), Err(ImageError::DuplicateChannel { index: 1 }));
+        assert_eq!(new_data.reorder_channels(&[0, 1, 2, 7]), Err(ImageError::OutOfBounds { index: 7, len: 4 }));
+        assert_eq!(new_data.pixel_values(1), Some(vec![4, 2, 3, 1]));
+    }
+
+    #[test]
+    fn imagedata_channel_labels() {
+        let mut new_data = Image::new(2);
+        new_data.create_channel(0u8);
+        new_data.create_channel(1u8);
+        new_data.create_channel(2u8);
+        new_data.set_channel_label(0, "depth").unwrap();
+        new_data.set_channel_label(2, String::from("mask")).unwrap();
+        assert_eq!(new_data.set_channel_label(3, "spot"), Err(ImageError::OutOfBounds { index: 3, len: 3 }));
+        assert_eq!(new_data.label_of(0), Some("depth"));
+        assert_eq!(new_data.label_of(1), None);
+
+        // Labels follow their channels around
+        new_data.reorder_channels(&[2, 0, 1]).unwrap();
+        assert_eq!(new_data.channel_by_label("mask").unwrap()[0], 2);
+        assert_eq!(new_data.channel_by_label("depth").unwrap()[0], 0);
+        new_data.retain_channels(|i, _| i != 0);
+        assert!(new_data.channel_by_label("mask").is_none());
+        assert_eq!(new_data.label_of(0), Some("depth"));
+
+        // The last channel to take a label keeps it
+        new_data.set_channel_label(1, "depth").unwrap();
+        assert_eq!(new_data.label_of(0), None);
+        assert_eq!(new_data.channel_by_label("depth").unwrap()[0], 1);
+        new_data.clone_channel(1).unwrap();
+        assert_eq!(new_data.label_of(2), None);
+    }
+
+    #[test]
+    fn imagedata_channel_iterators() {
+        let mut new_data = Image::new(3);
+        for v in 0..4u8 {
+            new_data.create_channel(v);
+        }
+        assert_eq!(new_data.channels().len(), 4);
+        for (i, c) in new_data.channels().enumerate() {
+            assert_eq!(c[0], i as u8);
+        }
+        for c in new_data.channels_mut() {
+            c.write(2, 9);
+        }
+        for i in 0..4 {
+            assert_eq!(new_data.channel(i).unwrap().to_vec(), vec![i as u8, i as u8, 9]);
+        }
+    }
+
+    #[test]
+    fn imagedata_retain_pixels() {
+        let mut new_data = Image::new_2d(3, 2);
+        new_data.create_channel(0u8);
+        new_data.create_channel(0u8);
+        new_data[0].apply_indexed(|i, x| *x = i as u8);
+        new_data[1].apply_indexed(|i, x| *x = 10 * i as u8);
+        new_data.set_channel_label(1, "tens").unwrap();
+
+        // Drop the middle column
+        new_data.retain_pixels(|i| i % 3 != 1);
+        assert_eq!((new_data.len(), new_data.width(), new_data.height()), (4, 4, 1));
+        assert_eq!(new_data[0].to_vec(), vec![0, 2, 3, 5]);
+        assert_eq!(new_data[1].to_vec(), vec![0, 20, 30, 50]);
+        assert_eq!(new_data.label_of(1), Some("tens"));
+
+        new_data.retain_pixels(|_| false);
+        assert_eq!(new_data.len(), 0);
+        assert_eq!(new_data[0].len(), 0);
+    }
+
+    #[test]
+    fn imagedata_with_channels() {
+        let new_data = Image::with_channels(3, &[0.0, 0.5, 1.0]);
+        assert_eq!(new_data.count(), 3);
+        assert_eq!(new_data.len(), 3);
+        assert_eq!(new_data.pixel_values(2), Some(vec![0.0, 0.5, 1.0]));
+
+        let new_data = Image::filled(2, 4, 7u8);
+        assert_eq!(new_data.count(), 4);
+        assert_eq!(new_data.as_raw_channels(), vec![&[7, 7][..]; 4]);
+
+        assert_eq!(Image::<u8>::with_channels(5, &[]).count(), 0);
+        let empty = Image::filled(0, 2, 1u8);
+        assert_eq!((empty.count(), empty.len(), empty[1].len()), (2, 0, 0));
+    }
+
+    #[test]
+    fn imagedata_clone_channel() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data[0].write(3, 21);
+        assert_eq!(new_data.clone_channel(0), Ok(1));
+        assert_eq!(new_data.count(), 2);
+        assert_eq!(new_data[1].to_vec(), new_data[0].to_vec());
+        assert_eq!(new_data.clone_channel(2), Err(ImageError::OutOfBounds { index: 2, len: 2 }));
+    }
+
+    #[test]
+    fn imagedata_retain_channels() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        new_data.create_channel(2);
+        new_data[2].write(0, 0);
+        // Drop the channel at index 1, and every channel that is constant
+        new_data.retain_channels(|i, c| i != 1 && c.iter().any(|x| *x != c[0]));
+        assert_eq!(new_data.count(), 1);
+        assert_eq!(new_data[0].iter().cloned().collect::<Vec<_>>(), vec![0,2,2,2,2]);
+    }
+
+    #[test]
+    fn imagedata_crop() {
+        let mut new_data = Image::new_2d(4, 4);
+        new_data.create_channel(0);
+        new_data.create_channel(7);
+        for i in 0..16 {
+            new_data[0].write(i, i);
+        }
+        new_data.set_channel_label(1, "mask").unwrap();
+        let cropped = new_data.crop(1, 1, 2, 2).unwrap();
+        assert_eq!((cropped.width(), cropped.height(), cropped.len()), (2, 2, 4));
+        assert_eq!(cropped[0].iter().cloned().collect::<Vec<_>>(), vec![5,6,9,10]);
+        assert_eq!(new_data.crop(3, 0, 2, 2).err(), Some(ImageError::RegionOutOfBounds { x: 3, y: 0, width: 2, height: 2 }));
+        assert_eq!(new_data.crop(0, 4, 1, 1).err(), Some(ImageError::RegionOutOfBounds { x: 0, y: 4, width: 1, height: 1 }));
+        // Rectangles that would overflow are out of bounds too
+        let max = ::std::usize::MAX;
+        assert_eq!(new_data.crop(max, 0, 2, 1).err(), Some(ImageError::RegionOutOfBounds { x: max, y: 0, width: 2, height: 1 }));
+        assert_eq!(new_data.crop(0, 1, 1, max).err(), Some(ImageError::RegionOutOfBounds { x: 0, y: 1, width: 1, height: max }));
+
+        // Defaults and labels come along
+        assert_eq!(cropped.label_of(1), Some("mask"));
+        let mut cleared = cropped.clone();
+        cleared.clear();
+        assert_eq!(cleared.pixel_values(0), Some(vec![0, 7]));
+
+        // A single pixel, a corner, and the whole image
+        let single = new_data.crop(2, 1, 1, 1).unwrap();
+        assert_eq!((single.len(), single.pixel_values(0)), (1, Some(vec![6, 7])));
+        let corner = new_data.crop(2, 2, 2, 2).unwrap();
+        assert_eq!(corner[0].to_vec(), vec![10, 11, 14, 15]);
+        assert_eq!(new_data.crop(0, 0, 4, 4).unwrap(), new_data);
+    }
+
+    #[test]
+    fn imagedata_2d_addressing() {
+        let mut new_data = Image::new_2d(3, 2);
+        new_data.create_channel(0u8);
+        new_data.create_channel(0u8);
+        // Rows are stored one after another
+        assert_eq!(new_data.index_of(0, 0), Some(0));
+        assert_eq!(new_data.index_of(2, 0), Some(2));
+        assert_eq!(new_data.index_of(0, 1), Some(3));
+        assert_eq!(new_data.index_of(2, 1), Some(5));
+        assert_eq!(new_data.index_of(3, 0), None);
+        assert_eq!(new_data.index_of(0, 2), None);
+
+        new_data.set_2d(1, 1, 1, 8).unwrap();
+        assert_eq!(new_data[1][4], 8);
+        assert_eq!(new_data.get_2d(1, 1, 1), Some(&8));
+        assert_eq!(new_data.get_2d(0, 1, 1), Some(&0));
+        assert_eq!(new_data.get_2d(2, 1, 1), None);
+        assert_eq!(new_data.get_2d(0, 3, 1), None);
+        assert_eq!(new_data.set_2d(0, 0, 2, 1), Err(ImageError::RegionOutOfBounds { x: 0, y: 2, width: 1, height: 1 }));
+        assert_eq!(new_data.set_2d(2, 0, 0, 1), Err(ImageError::OutOfBounds { index: 2, len: 2 }));
+
+        // 1D images are a single row
+        let line = Image::<u8>::new(4);
+        assert_eq!((line.width(), line.height()), (4, 1));
+        assert_eq!(line.index_of(3, 0), Some(3));
+    }
+
+    #[test]
+    fn imagedata_transpose() {
+        // 0 1
+        // 2 3
+        // 4 5
+        let mut new_data = Image::new_2d(2, 3);
+        new_data.create_channel(0);
+        new_data[0].apply_indexed(|i, x| *x = i);
+        let transposed = new_data.transpose().unwrap();
+        assert_eq!((transposed.width(), transposed.height()), (3, 2));
+        assert_eq!(transposed[0].to_vec(), vec![0,2,4,1,3,5]);
+        assert_eq!(transposed.transpose().unwrap(), new_data);
+    }
+
+    #[test]
+    fn imagedata_try_channel() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        assert!(new_data.try_channel(0).is_ok());
+        assert_eq!(new_data.try_channel(1).err(), Some(OutOfBoundsError { index: 1, len: 1 }));
+        new_data.try_channel_mut(0).unwrap().write(1, 21);
+        assert_eq!(new_data[0].get(1).cloned(), Some(21));
+        assert!(new_data.try_channel_mut(3).is_err());
+    }
+
+    #[test]
+    fn imagedata_dirty_tracking() {
+        let mut new_data = Image::new(10);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        assert_eq!(new_data.take_dirty(), None);
+        new_data[0].write(7, 1);
+        new_data[1].write(3, 1);
+        assert_eq!(new_data.take_dirty(), Some(3..8));
+        assert_eq!(new_data[0].take_dirty(), None);
+        assert_eq!(new_data.take_dirty(), None);
+    }
+
+    #[test]
+    fn imagedata_split() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        new_data.set_pixel_values(1, &[21, 22]).unwrap();
+        let parts = new_data.split();
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|p| p.count() == 1 && p.len() == 5));
+        assert_eq!(parts[0][0].to_vec(), vec![0,21,0,0,0]);
+        assert_eq!(parts[1][0].to_vec(), vec![1,22,1,1,1]);
+    }
+
+    #[test]
+    fn imagedata_merge() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        let mut other = Image::new(5);
+        other.create_channel(2);
+        assert_eq!(new_data.merge(other), Ok(()));
+        assert_eq!(new_data.count(), 3);
+        assert_eq!(new_data.pixel_values(0), Some(vec![0, 1, 2]));
+
+        let mut short = Image::new(4);
+        short.create_channel(3);
+        assert_eq!(new_data.merge(short), Err(ImageError::LengthMismatch { expected: 5, got: 4 }));
+        assert_eq!(new_data.count(), 3);
+
+        // 2D images must line up, not just hold as many values
+        let mut wide = Image::filled(6, 1, 0u8).reshaped(3, 2);
+        let tall = Image::filled(6, 1, 1u8).reshaped(2, 3);
+        assert_eq!(wide.merge(tall), Err(ImageError::LengthMismatch { expected: 3, got: 2 }));
+        assert_eq!(wide.count(), 1);
+        assert_eq!(wide.merge(Image::filled(6, 1, 2u8)), Ok(()));
+        assert_eq!(wide.merge(Image::filled(6, 1, 3u8).reshaped(3, 2)), Ok(()));
+        assert_eq!(wide.count(), 3);
+    }
+
+    #[test]
+    fn imagedata_append() {
+        let mut strip = Image::new(2);
+        strip.create_channel(0);
+        strip.create_channel(1);
+        let mut tile = Image::new(3);
+        tile.create_channel(5);
+        tile.create_channel(6);
+        assert_eq!(strip.append(&tile), Ok(()));
+        assert_eq!((strip.len(), strip.width(), strip.height()), (5, 5, 1));
+        assert_eq!(strip[0].to_vec(), vec![0, 0, 5, 5, 5]);
+        assert_eq!(strip[1].to_vec(), vec![1, 1, 6, 6, 6]);
+
+        let mut one = Image::new(5);
+        one.create_channel(9);
+        assert_eq!(strip.append(&one), Err(ImageError::ChannelCountMismatch { expected: 2, got: 1 }));
+        assert_eq!(strip.len(), 5);
+
+        // An empty image takes the layout of whatever is appended, and adds nothing itself
+        let mut empty = Image::filled(0, 1, 0u8).reshaped(0, 0);
+        let block = Image::filled(6, 1, 4u8).reshaped(3, 2);
+        assert_eq!(empty.append(&block), Ok(()));
+        assert_eq!((empty.len(), empty.width(), empty.height()), (6, 3, 2));
+        assert_eq!(empty.append(&Image::filled(0, 1, 0u8)), Ok(()));
+        assert_eq!((empty.len(), empty.width(), empty.height()), (6, 3, 2));
+        assert_eq!(empty[0].to_vec(), vec![4; 6]);
+        assert_eq!(empty.append(&Image::filled(4, 1, 5u8).reshaped(2, 2)), Err(ImageError::WidthMismatch { expected: 3, got: 2 }));
+    }
+
+    #[test]
+    fn imagedata_concat() {
+        let top = Image::filled(2, 1, 1u8).reshaped(2, 1);
+        let bottom = Image::filled(4, 1, 2u8).reshaped(2, 2);
+        let joined = concat(&top, &bottom).unwrap();
+        assert_eq!((joined.len(), joined.width(), joined.height()), (6, 2, 3));
+        assert_eq!(joined[0].to_vec(), vec![1, 1, 2, 2, 2, 2]);
+        assert_eq!(joined.get_2d(0, 1, 2), Some(&2));
+        // Rows wouldn't line up
+        let wide = Image::filled(6, 1, 3u8).reshaped(3, 2);
+        assert_eq!(concat(&bottom, &wide), Err(ImageError::WidthMismatch { expected: 2, got: 3 }));
+        assert_eq!(top.len(), 2);
+    }
+
+    #[test]
+    fn imagedata_map() {
+        let mut new_data = Image::filled(6, 2, 0.5f32).reshaped(3, 2);
+        new_data[0].write(1, 1.0);
+        new_data.set_channel_label(1, "alpha").unwrap();
+        // Color is scaled to the full byte range, alpha only to 100
+        let bytes: Image<u8> = new_data.map(|c, v| if c == 0 { (v * 255.0).round() as u8 } else { (v * 100.0) as u8 });
+        assert_eq!((bytes.len(), bytes.width(), bytes.height(), bytes.count()), (6, 3, 2, 2));
+        assert_eq!(bytes[0].to_vec(), vec![128, 255, 128, 128, 128, 128]);
+        assert_eq!(bytes[1].to_vec(), vec![50; 6]);
+        assert_eq!(bytes.channel_by_label("alpha").map(|c| c.to_vec()), Some(vec![50; 6]));
+        // Defaults are mapped too
+        let mut grown = bytes.clone();
+        grown.resize(7);
+        assert_eq!((grown[0][6], grown[1][6]), (128, 50));
+    }
+
+    #[test]
+    fn imagedata_select_channels() {
+        let mut new_data = Image::filled(4, 3, 0u8).reshaped(2, 2);
+        for i in 0..3 {
+            new_data[i].write(0, i as u8 + 1);
+        }
+        new_data.set_channel_label(2, "blue").unwrap();
+        let reversed = new_data.select_channels(&[2, 1, 0]).unwrap();
+        assert_eq!((reversed.len(), reversed.width(), reversed.count()), (4, 2, 3));
+        assert_eq!(reversed.pixel_values(0), Some(vec![3, 2, 1]));
+        assert_eq!(reversed.label_of(0), Some("blue"));
+
+        let doubled = new_data.select_channels(&[2, 2]).unwrap();
+        assert_eq!(doubled[0].to_vec(), doubled[1].to_vec());
+        assert_eq!((doubled.label_of(0), doubled.label_of(1)), (Some("blue"), None));
+
+        assert_eq!(new_data.select_channels(&[0, 3]).err(), Some(ImageError::OutOfBounds { index: 3, len: 3 }));
+    }
+
+    #[test]
+    fn imagedata_split_merge_all() {
+        let mut new_data = Image::filled(6, 3, 0u8).reshaped(3, 2);
+        new_data.set_pixel_values(4, &[1, 2, 3]).unwrap();
+        new_data.set_channel_label(1, "mask").unwrap();
+        let parts = new_data.clone().split();
+        assert_eq!(parts[1].label_of(0), Some("mask"));
+        assert_eq!((parts[2].width(), parts[2].height()), (3, 2));
+        assert_eq!(Image::merge_all(parts), Ok(new_data.clone()));
+
+        let two = Image::filled(6, 2, 0u8);
+        assert_eq!(Image::merge_all(vec![two]), Err(ImageError::ChannelCountMismatch { expected: 1, got: 2 }));
+        let parts = vec![Image::filled(6, 1, 0u8), Image::filled(5, 1, 0u8)];
+        assert_eq!(Image::merge_all(parts), Err(ImageError::LengthMismatch { expected: 6, got: 5 }));
+        assert_eq!(Image::<u8>::merge_all(vec![]).map(|i| i.count()), Ok(0));
+    }
+
+    #[test]
+    fn imagedata_zip_apply() {
+        let mut base = Image::filled(3, 2, 1u8);
+        let mut layer = Image::filled(3, 2, 0u8);
+        layer[0].write(1, 5);
+        layer[1].fill(2);
+        base.zip_apply(&layer, |_, a, b| *a += *b).unwrap();
+        assert_eq!(base[0].to_vec(), vec![1, 6, 1]);
+        assert_eq!(base[1].to_vec(), vec![3, 3, 3]);
+
+        // The channel index is passed along
+        base.zip_apply(&layer, |c, a, _| *a = c as u8).unwrap();
+        assert_eq!(base.pixel_values(0), Some(vec![0, 1]));
+
+        assert_eq!(base.zip_apply(&Image::filled(4, 2, 0), |_, _, _| {}), Err(ImageError::LengthMismatch { expected: 3, got: 4 }));
+        assert_eq!(base.zip_apply(&Image::filled(3, 1, 0), |_, _, _| {}), Err(ImageError::ChannelCountMismatch { expected: 2, got: 1 }));
+    }
+
+    #[test]
+    fn imagedata_clear() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        new_data.set_pixel_values(1, &[21, 22]).unwrap();
+        new_data[0].write(4, 42);
+        new_data.clear();
+        assert_eq!((new_data.count(), new_data.len()), (2, 5));
+        assert_eq!(new_data[0].to_vec(), vec![0,0,0,0,0]);
+        assert_eq!(new_data[1].to_vec(), vec![1,1,1,1,1]);
+    }
+
+    #[test]
+    fn imagedata_eq() {
+        let build = || {
+            let mut new_data = Image::new(5);
+            new_data.create_channel(0);
+            new_data.create_channel(1);
+            new_data[1].write(3, 21);
+            new_data
+        };
+        let mut new_data = build();
+        assert_eq!(new_data, build());
+        new_data[0].write(0, 1);
+        assert!(new_data != build());
+        assert!(Image::<u8>::new(4) != Image::new(5));
+        assert!(Image::<u8>::new_2d(2, 3) != Image::new_2d(3, 2));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn imagedata_serde() {
+        use bincode;
+
+        let mut new_data = Image::new_2d(3, 2);
+        new_data.create_channel(0u8);
+        new_data.create_channel(1u8);
+        new_data.set_pixel_values(4, &[21, 22]).unwrap();
+        let bytes = bincode::serialize(&new_data).unwrap();
+        let read: Image<u8> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(read, new_data);
+
+        // Channels that don't match the image length are rejected
+        let mut bad = new_data.clone();
+        bad.channels[1] = Channel::new(1u8, 4);
+        let bytes = bincode::serialize(&bad).unwrap();
+        assert!(bincode::deserialize::<Image<u8>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn imagedata_fill_all() {
+        let mut new_data = Image::new(5);
+        new_data.create_channel(0);
+        new_data.create_channel(1);
+        assert_eq!(new_data.fill_all(&[7, 8]), Ok(()));
+        assert_eq!(new_data[0].to_vec(), vec![7; 5]);
+        assert_eq!(new_data[1].to_vec(), vec![8; 5]);
+        assert_eq!(new_data.fill_all(&[9]), Err(ImageError::ChannelCountMismatch { expected: 2, got: 1 }));
+        assert_eq!(new_data[0].to_vec(), vec![7; 5]);
+        // Defaults are untouched
+        new_data.clear();
+        assert_eq!(new_data.pixel_values(0), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn imagedata_fill_channel() {
+        let mut new_data = Image::filled(4, 2, 0u8);
+        assert_eq!(new_data.fill_channel(1, 3), Ok(()));
+        assert_eq!(new_data[0].to_vec(), vec![0; 4]);
+        assert_eq!(new_data[1].to_vec(), vec![3; 4]);
+        assert_eq!(new_data.fill_channel(2, 3), Err(ImageError::OutOfBounds { index: 2, len: 2 }));
+    }
+
     #[test]
     fn imagedata_channel_length() {
         let mut new_data = Image::new(5);
@@ -302,4 +2855,22 @@ mod tests {
         assert_eq!(new_data.len(), new_data[0].len());
         assert_eq!(new_data.len(), new_data[1].len());
     }
+
+    #[test]
+    fn channel_debug_summary() {
+        let chan = Channel::new(0u8, 4096 * 2160);
+        let summary = format!("{:?}", chan);
+        assert!(summary.len() < 100);
+        assert_eq!(summary, "Channel { len: 8847360, default: 0, data: [0, 0, 0, 0, ..., 0, 0, 0, 0] }");
+        // Short channels are printed whole
+        assert_eq!(format!("{:?}", Channel::new(1u8, 2)), "Channel { len: 2, default: 1, data: [1, 1] }");
+        assert!(format!("{:?}", Channel::new(0u8, 10).full_debug()).contains("[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]"));
+
+        let mut image = Image::new(4096 * 2160);
+        image.create_channel(0u8);
+        image.create_channel(255u8);
+        let summary = format!("{:?}", image);
+        assert!(summary.starts_with("Image { count: 2, len: 8847360,"));
+        assert!(summary.len() < 250);
+    }
 }
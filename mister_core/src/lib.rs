@@ -4,12 +4,21 @@
 // NOTE: Once we reach version 1.0, change from warn to deny
 extern crate byteorder;
 extern crate palette;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate bincode;
+#[cfg(feature = "fft")]
+extern crate num_complex;
 
 pub mod image; // Where all image-storing stuff goes
+pub mod sparse;
 pub mod project;
 pub mod format;
 
 pub use self::image::{Channel, Image};
+pub use self::sparse::SparseChannel;
 pub use self::format::{RgbaImage, ImageFormat};
 
 // How will we support a "palette-only" mode. For those kinds of things, we turn to palette, as
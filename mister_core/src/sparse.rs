@@ -0,0 +1,217 @@
+//! Run-length encoded channels, for data that is constant over large stretches.
+
+use image::Channel;
+use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::mem;
+
+/// A stretch of equal values, ending (exclusively) at `end`
+#[derive(Clone, Debug)]
+struct Run<T> {
+    end: usize,
+    value: T,
+}
+
+/// A channel that stores runs of equal values instead of every value.
+/// It has the same read API as `Channel`, and writes only split the run they land in.
+// NOTE: Alpha channels and selection masks are mostly constant, so this saves lots of memory
+#[derive(Clone, Debug)]
+pub struct SparseChannel<T: Clone + Debug + PartialEq> {
+    // NOTE: runs are sorted, never empty, and no two neighbours hold the same value
+    runs: Vec<Run<T>>,
+    len: usize,
+
+    default: T, // Hold onto this, we need it for resizing.
+}
+
+impl<T: Clone + Debug + PartialEq> SparseChannel<T> {
+    /// Creates a new SparseChannel
+    pub fn new(default: T, x: usize) -> SparseChannel<T> {
+        let runs = if x > 0 { vec![Run { end: x, value: default.clone() }] } else { vec![] };
+        SparseChannel {
+            runs: runs,
+            len: x,
+            default: default
+        }
+    }
+
+    /// Get the length of data in this channel
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the number of runs used to store the data
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Get the number of bytes allocated to store the runs
+    pub fn heap_size(&self) -> usize {
+        self.runs.capacity() * mem::size_of::<Run<T>>()
+    }
+
+    /// Find the run holding index `i`
+    fn run_at(&self, i: usize) -> Option<usize> {
+        if i >= self.len {
+            return None
+        }
+        match self.runs.binary_search_by(|r| if r.end <= i { Ordering::Less } else { Ordering::Greater }) {
+            Ok(r) | Err(r) => Some(r)
+        }
+    }
+
+    /// Retrieve value at index `i`
+    pub fn get(&self, i: usize) -> Option<&T> {
+        self.run_at(i).map(|r| &self.runs[r].value)
+    }
+
+    /// Change value at index `i` to `data`
+    // NOTE PANICS if i is out of bounds, like Channel::write
+    pub fn write(&mut self, i: usize, data: T) {
+        let r = self.run_at(i).expect("SparseChannel::write index out of bounds");
+        if self.runs[r].value == data {
+            return
+        }
+        let start = if r == 0 { 0 } else { self.runs[r - 1].end };
+        let Run { end, value } = self.runs[r].clone();
+
+        // Split the run around i
+        let mut pieces = vec![];
+        if i > start {
+            pieces.push(Run { end: i, value: value.clone() });
+        }
+        let at = r + pieces.len();
+        pieces.push(Run { end: i + 1, value: data });
+        if end > i + 1 {
+            pieces.push(Run { end: end, value: value });
+        }
+        self.runs.splice(r..r+1, pieces);
+
+        // The new run may now match its neighbours
+        if at + 1 < self.runs.len() && self.runs[at + 1].value == self.runs[at].value {
+            let next = self.runs.remove(at + 1);
+            self.runs[at].end = next.end;
+        }
+        if at > 0 && self.runs[at - 1].value == self.runs[at].value {
+            let cur = self.runs.remove(at);
+            self.runs[at - 1].end = cur.end;
+        }
+    }
+
+    /// Resize channel to `new_len`, filling new space with the default
+    pub fn resize(&mut self, new_len: usize) {
+        if new_len == 0 {
+            self.runs.clear();
+        } else if new_len < self.len {
+            // Keep up to the run holding the new last value
+            let r = self.run_at(new_len - 1).unwrap();
+            self.runs.truncate(r + 1);
+            self.runs[r].end = new_len;
+        } else if new_len > self.len {
+            let extend = self.runs.last().map(|r| r.value == self.default).unwrap_or(false);
+            if extend {
+                self.runs.last_mut().unwrap().end = new_len;
+            } else {
+                self.runs.push(Run { end: new_len, value: self.default.clone() });
+            }
+        }
+        self.len = new_len;
+    }
+
+    /// Create an iterator over the values of this channel
+    pub fn iter(&self) -> SparseChannelIterator<T> {
+        SparseChannelIterator {
+            chan: self,
+            run: 0,
+            at: 0
+        }
+    }
+
+    /// Expand into a regular channel
+    pub fn to_dense(&self) -> Channel<T> {
+        Channel::from_vec(self.default.clone(), self.iter().cloned().collect())
+    }
+}
+
+/// Iterates over the data of a sparse channel
+pub struct SparseChannelIterator<'a, T: Clone + Debug + PartialEq + 'a> {
+    chan: &'a SparseChannel<T>,
+    run: usize,
+    at: usize
+}
+
+impl<'a, T: Clone + Debug + PartialEq + 'a> Iterator for SparseChannelIterator<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        if self.at >= self.chan.len() {
+            return None
+        }
+        if self.at >= self.chan.runs[self.run].end {
+            self.run += 1;
+        }
+        self.at += 1;
+        Some(&self.chan.runs[self.run].value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.chan.len() - self.at;
+        (left, Some(left))
+    }
+}
+impl<'a, T: Clone + Debug + PartialEq + 'a> ExactSizeIterator for SparseChannelIterator<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseChannel;
+    use image::Channel;
+
+    #[test]
+    fn sparse_matches_dense() {
+        let mut dense = Channel::new(0u8, 10);
+        let mut sparse = SparseChannel::new(0u8, 10);
+        for &(i, v) in [(4, 21), (5, 21), (0, 3), (9, 7), (4, 0), (5, 0), (8, 7)].iter() {
+            dense.write(i, v);
+            sparse.write(i, v);
+            assert_eq!(sparse.iter().cloned().collect::<Vec<_>>(), dense.to_vec());
+        }
+        for i in 0..11 {
+            assert_eq!(sparse.get(i), dense.get(i));
+        }
+        assert_eq!(sparse.len(), dense.len());
+        assert_eq!(sparse.iter().len(), dense.iter().len());
+        // [3], [0 x 7], [7 x 2]
+        assert_eq!(sparse.run_count(), 3);
+        assert_eq!(sparse.to_dense().to_vec(), dense.to_vec());
+    }
+
+    #[test]
+    fn sparse_resize() {
+        let mut dense = Channel::new(1u8, 4);
+        let mut sparse = SparseChannel::new(1u8, 4);
+        dense.write(3, 5);
+        sparse.write(3, 5);
+        dense = dense.resize(6);
+        sparse.resize(6);
+        assert_eq!(sparse.iter().cloned().collect::<Vec<_>>(), dense.to_vec());
+        // Cut exactly at the end of a run
+        dense = dense.resize(3);
+        sparse.resize(3);
+        assert_eq!(sparse.iter().cloned().collect::<Vec<_>>(), dense.to_vec());
+        assert_eq!(sparse.run_count(), 1);
+        dense = dense.resize(2);
+        sparse.resize(2);
+        assert_eq!(sparse.iter().cloned().collect::<Vec<_>>(), dense.to_vec());
+        assert_eq!(sparse.run_count(), 1);
+        sparse.resize(0);
+        assert_eq!(sparse.iter().next(), None);
+    }
+
+    #[test]
+    fn sparse_constant_is_small() {
+        let sparse = SparseChannel::new(1.0f32, 10_000_000);
+        assert_eq!(sparse.len(), 10_000_000);
+        assert_eq!(sparse.run_count(), 1);
+        assert!(sparse.heap_size() < 64);
+        assert_eq!(sparse.get(9_999_999).cloned(), Some(1.0));
+    }
+}